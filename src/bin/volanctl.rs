@@ -0,0 +1,36 @@
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::process;
+
+use volanwm::{ipc, Result};
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() {
+        eprintln!("Usage: volanctl <command> [args...]");
+        eprintln!("Examples: volanctl FocusNext | volanctl switch_group beta | volanctl subscribe");
+        process::exit(2);
+    }
+
+    let mut stream: UnixStream = UnixStream::connect(ipc::socket_path()?)?;
+    // Sent as JSON to keep arguments with spaces in them whole.
+    writeln!(stream, "{}", serde_json::to_string(&args)?)?;
+
+    let mut reader = BufReader::new(&stream);
+    let mut reply: String = String::new();
+    reader.read_line(&mut reply)?;
+    print!("{}", reply);
+
+    let parsed: ipc::Reply = serde_json::from_str(&reply)?;
+    if !parsed.success {
+        process::exit(1);
+    }
+
+    // Subscribers keep receiving events until the WM goes away.
+    if args[0] == "subscribe" {
+        for line in reader.lines() {
+            println!("{}", line?);
+        }
+    }
+    Ok(())
+}
@@ -1,33 +1,247 @@
 #[macro_use]
 extern crate volanwm;
 
-use volanwm::layout::*;
-use volanwm::{config, gen_workspaces, Result, Volan};
-
-fn main() -> Result<()> {
-    volanwm::intiailize_logger()?;
-    let parser = config::Parser::new();
+use std::env;
+use std::path::PathBuf;
+use std::process;
+use std::str::FromStr;
 
-    let keys_bound_to_commands: Vec<config::BoundCommand> = parser.get_bound_commands();
-    let group_defs: Vec<config::BoundWorkSpace> = parser.get_bound_workspaces();
-    let (innergaps, outergaps): (u32, u32) = parser.get_gaps();
+use log::LevelFilter;
+use volanwm::layout::*;
+use volanwm::{config, KeyHandlers, LogOptions, Result, Settings, Volan};
 
-    let layouts: Vec<Box<dyn volanwm::layout::Layout>> = layouts![
+fn layouts(innergaps: u32, outergaps: u32) -> Vec<Box<dyn Layout>> {
+    layouts![
         TileLayout::new("tile", innergaps, outergaps),
         CenterMaster::new("c_master", innergaps, outergaps),
-    ];
+    ]
+}
+
+const USAGE: &str = "Usage: volanwm [OPTIONS]
+
+Options:
+    --config <FILE>       Read the config from FILE instead of
+                          $XDG_CONFIG_HOME/volan/config.yaml [VOLAN_CONFIG]
+    --log-level <LEVEL>   One of off, error, warn, info, debug or trace
+                          [VOLAN_LOG_LEVEL] (default: trace)
+    --log-file <FILE>     Append the log to FILE instead of
+                          $XDG_DATA_HOME/volan/volan.log [VOLAN_LOG_FILE]
+    --no-stdout-log       Only log to the log file [VOLAN_NO_STDOUT_LOG]
+    --check-config [FILE] Check the config and print its bindings, without
+                          connecting to X
+    --help                Print this message
 
-    let (keys_bound_to_commands_with_group_bindings, workspaces): (
-        Vec<config::BoundCommand>,
-        Vec<volanwm::WorkSpaceBuilder>,
-    ) = gen_workspaces(keys_bound_to_commands, group_defs);
+The control socket is $XDG_RUNTIME_DIR/volan/volan$DISPLAY.sock, unless
+VOLAN_SOCKET is set to another path.";
+
+/// What to do, from the command line and the environment.
+struct Options {
+    config: Option<PathBuf>,
+    check_config: bool,
+    log: LogOptions,
+}
+
+impl Options {
+    /// Reads the `VOLAN_*` environment variables and then the arguments,
+    /// which take precedence over them.
+    fn parse(args: Vec<String>) -> std::result::Result<Options, String> {
+        let mut options = Options {
+            config: env::var_os("VOLAN_CONFIG").map(PathBuf::from),
+            check_config: false,
+            log: LogOptions {
+                file: env::var_os("VOLAN_LOG_FILE").map(PathBuf::from),
+                stdout: env::var("VOLAN_NO_STDOUT_LOG")
+                    .map_or(true, |value| value.is_empty() || value == "0"),
+                ..LogOptions::default()
+            },
+        };
+        if let Ok(level) = env::var("VOLAN_LOG_LEVEL") {
+            options.log.level = parse_level(&level)?;
+        }
+
+        let mut args = args.into_iter().peekable();
+        while let Some(arg) = args.next() {
+            let mut value =
+                |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+            match arg.as_str() {
+                "--config" => options.config = Some(PathBuf::from(value(&arg)?)),
+                "--log-level" => options.log.level = parse_level(&value(&arg)?)?,
+                "--log-file" => options.log.file = Some(PathBuf::from(value(&arg)?)),
+                "--no-stdout-log" => options.log.stdout = false,
+                "--check-config" => {
+                    options.check_config = true;
+                    if let Some(path) = args.next_if(|next| !next.starts_with("--")) {
+                        options.config = Some(PathBuf::from(path));
+                    }
+                }
+                _ => return Err(format!("unknown argument `{}`", arg)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn parse_level(level: &str) -> std::result::Result<LevelFilter, String> {
+    LevelFilter::from_str(level).map_err(|_| format!("unknown log level `{}`", level))
+}
 
-    Volan::new(
-        keys_bound_to_commands_with_group_bindings,
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    let options: Options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("volanwm: {}\n\n{}", error, USAGE);
+            process::exit(2);
+        }
+    };
+    if options.check_config {
+        process::exit(check_config(options.config));
+    }
+
+    volanwm::intiailize_logger(&options.log)?;
+    let template: Vec<Box<dyn Layout>> = layouts(0, 0);
+    let layout_names: Vec<&str> = template.iter().map(|layout| layout.name()).collect();
+    let parser = config::Parser::new(options.config.as_deref(), &layout_names);
+    volanwm::set_log_rotation(parser.get_log_rotation());
+    if !parser.errors().is_empty() {
+        config::notify_errors(parser.errors());
+    }
+
+    let (keys, workspaces): (KeyHandlers, Vec<volanwm::WorkSpaceBuilder>) =
+        volanwm::load_bindings(&parser);
+    let mouse_bindings: Vec<config::BoundMouseAction> = parser.get_bound_mouse_actions();
+    let (innergaps, outergaps): (u32, u32) = parser.get_gaps();
+    let settings = Settings {
+        focus_policy: parser.get_focus_policy(),
+        workspace_switching: parser.get_workspace_switching(),
+        borders: parser.get_borders(),
+        config_path: options.config,
+    };
+
+    let mut wm: Volan = Volan::new(
+        keys,
+        mouse_bindings,
         workspaces,
-        &layouts,
-    )?
-    .run();
+        &layouts(innergaps, outergaps),
+        settings,
+    )?;
+    wm.autostart(&parser.get_autostart(), true);
+    wm.run();
 
     Ok(())
 }
+
+/// Checks a config file without connecting to X, printing every error and
+/// every binding. Returns the exit status: 0 if the config is valid, 1 if it
+/// has errors and 2 if it couldn't be read.
+fn check_config(path: Option<PathBuf>) -> i32 {
+    let path: PathBuf = match path.or_else(config::Parser::default_path) {
+        Some(path) => path,
+        None => {
+            eprintln!("No config file found");
+            return 2;
+        }
+    };
+    let layouts: Vec<Box<dyn Layout>> = layouts(0, 0);
+    let layout_names: Vec<&str> = layouts.iter().map(|layout| layout.name()).collect();
+    let parser = match config::Parser::lint_file(&path, &layout_names) {
+        Ok(Ok(parser)) => parser,
+        Ok(Err(errors)) => {
+            print_errors(&path, &errors);
+            return 1;
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            return 2;
+        }
+    };
+
+    print_bindings(parser.describe_bindings());
+    let (innergaps, outergaps): (u32, u32) = parser.get_gaps();
+    println!();
+    println!("Gaps: inner {}, outer {}", innergaps, outergaps);
+    println!("Focus policy: {:?}", parser.get_focus_policy());
+    println!(
+        "Workspace switching: {:?}",
+        parser.get_workspace_switching()
+    );
+    println!("Borders: {:?}", parser.get_borders());
+    println!("Log rotation: {:?}", parser.get_log_rotation());
+    for program in parser.get_autostart() {
+        let command_line: Vec<&str> = std::iter::once(program.command.as_str())
+            .chain(program.args.iter().map(String::as_str))
+            .collect();
+        println!(
+            "Autostart{}: {}{}",
+            if program.once { " once" } else { "" },
+            command_line.join(" "),
+            program
+                .workspace
+                .map(|workspace| format!(" on workspace {}", workspace))
+                .unwrap_or_default(),
+        );
+    }
+
+    if parser.errors().is_empty() {
+        println!("{}: OK", path.display());
+        0
+    } else {
+        print_errors(&path, parser.errors());
+        1
+    }
+}
+
+/// Prints errors in the `file:line:column: message` form understood by
+/// editors.
+fn print_errors(path: &std::path::Path, errors: &[config::ConfigError]) {
+    for error in errors {
+        match error.position {
+            Some((line, column)) => {
+                eprintln!("{}:{}:{}: {}", path.display(), line, column, error.message)
+            }
+            None => eprintln!("{}: {}", path.display(), error.message),
+        }
+    }
+    eprintln!("{}: {} error(s)", path.display(), errors.len());
+}
+
+fn print_bindings(bindings: &[config::BindingDescription]) {
+    let rows: Vec<[String; 4]> = bindings
+        .iter()
+        .map(|binding| {
+            [
+                binding.mode.clone().unwrap_or_else(|| "-".to_string()),
+                binding.keys.clone(),
+                String::from(if binding.on_release {
+                    "release"
+                } else {
+                    "press"
+                }),
+                binding.action.clone(),
+            ]
+        })
+        .collect();
+    let header: [String; 4] = ["MODE", "KEYS", "ON", "ACTION"].map(String::from);
+    let mut widths: [usize; 4] = [0; 4];
+    for row in std::iter::once(&header).chain(rows.iter()) {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    for row in std::iter::once(&header).chain(rows.iter()) {
+        println!(
+            "{:<w0$}  {:<w1$}  {:<w2$}  {}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+        );
+    }
+}
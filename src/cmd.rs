@@ -26,6 +26,7 @@ pub mod lazy {
     pub fn focus_next() -> Command {
         Rc::new(|ref mut wm| {
             wm.group_mut().focus_next();
+            wm.update_ewmh();
             Ok(())
         })
     }
@@ -76,6 +77,7 @@ pub mod lazy {
     pub fn focus_previous() -> Command {
         Rc::new(|ref mut wm| {
             wm.group_mut().focus_previous();
+            wm.update_ewmh();
             Ok(())
         })
     }
@@ -88,6 +90,24 @@ pub mod lazy {
         })
     }
 
+    /// Floats the focused window, or tiles it if it is already floating.
+    pub fn toggle_floating() -> Command {
+        Rc::new(|ref mut wm| {
+            wm.group_mut().toggle_floating_focused();
+            wm.update_ewmh();
+            Ok(())
+        })
+    }
+
+    /// Toggles whether the focused window covers the whole screen.
+    pub fn toggle_fullscreen() -> Command {
+        Rc::new(|ref mut wm| {
+            wm.group_mut().toggle_fullscreen_focused();
+            wm.update_ewmh();
+            Ok(())
+        })
+    }
+
     /// Spawns the specified command.
     /// The returned `Command` will spawn the `Command` each time it is called.
     pub fn spawn(cmd: String, args: Vec<String>) -> Command {
@@ -105,6 +125,59 @@ pub mod lazy {
         })
     }
 
+    /// Moves the focus to the next monitor.
+    pub fn focus_monitor_next() -> Command {
+        Rc::new(|ref mut wm| {
+            wm.focus_monitor(1);
+            Ok(())
+        })
+    }
+
+    /// Moves the focus to the previous monitor.
+    pub fn focus_monitor_previous() -> Command {
+        Rc::new(|ref mut wm| {
+            wm.focus_monitor(-1);
+            Ok(())
+        })
+    }
+
+    /// Moves the focused window to the group shown on the next monitor.
+    pub fn move_window_to_monitor() -> Command {
+        Rc::new(|ref mut wm| {
+            wm.move_focused_to_monitor(1);
+            Ok(())
+        })
+    }
+
+    /// Swaps the group on the focused monitor with the one on the next.
+    pub fn swap_workspaces_between_monitors() -> Command {
+        Rc::new(|ref mut wm| {
+            wm.swap_workspaces_between_monitors(1);
+            Ok(())
+        })
+    }
+
+    /// Reads the config file again and applies it.
+    pub fn reload_config() -> Command {
+        Rc::new(|ref mut wm| wm.reload_config())
+    }
+
+    /// Switches to the named keymap mode.
+    pub fn enter_mode(name: String) -> Command {
+        Rc::new(move |wm| {
+            wm.set_mode(Some(name.clone()));
+            Ok(())
+        })
+    }
+
+    /// Leaves the current keymap mode.
+    pub fn exit_mode() -> Command {
+        Rc::new(|ref mut wm| {
+            wm.set_mode(None);
+            Ok(())
+        })
+    }
+
     /// Switches to the group specified by name.
     pub fn switch_group(name: String) -> Command {
         Rc::new(move |wm| {
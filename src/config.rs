@@ -1,30 +1,262 @@
 use crate::cmd::Command;
+use crate::mouse::{Button, MouseAction};
+use crate::settings::{Borders, FocusPolicy, LogRotation, WorkspaceSwitching};
 use crate::ModKey;
-use std::collections::HashMap;
+use config_deserializer::{Entry, Trigger};
+use failure::ResultExt;
+use locate::{Locator, Position};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::str::FromStr;
 type LayoutName = String;
 type WorkSpaceName = String;
 pub type XKeyValue = u32;
 pub type BoundCommand = (Vec<ModKey>, XKeyValue, Command);
+pub type Chord = (Vec<ModKey>, XKeyValue);
+pub type BoundSequence = (Vec<Chord>, Command);
+pub type BoundMode = (ModeName, Vec<BoundSequence>);
+type ModeName = String;
 pub type BoundWorkSpace = (ModKey, XKeyValue, WorkSpaceName, LayoutName);
+pub type BoundMouseAction = (Vec<ModKey>, Button, MouseAction);
 type Innergaps = u32;
 type Outergaps = u32;
 
 mod config_file_handler;
 mod key_parse;
+mod locate;
+
+/// A problem found in the config file.
+#[derive(Clone, Debug)]
+pub struct ConfigError {
+    /// Where the problem is, if it could be found.
+    pub position: Option<Position>,
+    pub message: String,
+}
+
+impl ConfigError {
+    /// Converts an error from parsing the whole file, which knows where it
+    /// happened.
+    fn from_yaml(error: &serde_yaml::Error) -> ConfigError {
+        let message: String = error.to_string();
+        match error.location() {
+            Some(location) => ConfigError {
+                position: Some((location.line(), location.column())),
+                // The position is already at the end of the message.
+                message: match message.rfind(" at line ") {
+                    Some(end) => message[..end].to_string(),
+                    None => message,
+                },
+            },
+            None => ConfigError {
+                position: None,
+                message,
+            },
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some((line, column)) => write!(f, "line {}, column {}: {}", line, column, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Shows errors in the config file in a desktop notification, as the log is
+/// unlikely to be seen when logging in.
+pub fn notify_errors(errors: &[ConfigError]) {
+    let body: String = errors
+        .iter()
+        .map(|error| error.to_string())
+        .collect::<Vec<String>>()
+        .join("\n");
+    let result = std::process::Command::new("notify-send")
+        .arg("--urgency=critical")
+        .arg("volanwm: errors in config.yaml")
+        .arg(body)
+        .spawn();
+    if let Err(error) = result {
+        error!("Could not show config errors with notify-send: {}", error);
+    }
+}
+
+/// A program to start with the window manager.
+#[derive(Clone, Debug)]
+pub struct Autostart {
+    pub command: String,
+    pub args: Vec<String>,
+    /// Whether to start it only when the window manager starts, rather than
+    /// again whenever the config is reloaded.
+    pub once: bool,
+    /// The workspace its first window goes to, rather than the current one.
+    pub workspace: Option<WorkSpaceName>,
+}
+
+/// A binding as resolved from the config, for listing.
+#[derive(Clone, Debug)]
+pub struct BindingDescription {
+    /// The mode the binding is in, or `None` for the usual keymap.
+    pub mode: Option<String>,
+    /// The keys or button, normalised, e.g. `Mod1+Shift+Return`.
+    pub keys: String,
+    pub on_release: bool,
+    pub action: String,
+}
+
+/// Everything bound to keys or buttons in the config.
+#[derive(Default)]
+struct Bindings {
+    commands: Vec<BoundCommand>,
+    sequences: Vec<BoundSequence>,
+    release_sequences: Vec<BoundSequence>,
+    modes: Vec<BoundMode>,
+    workspaces: Vec<BoundWorkSpace>,
+    mouse_actions: Vec<BoundMouseAction>,
+}
 
 pub struct Parser {
     deserialized_config: config_deserializer::Config,
+    bindings: Bindings,
+    borders: Borders,
+    log_rotation: LogRotation,
+    autostart: Vec<Autostart>,
+    descriptions: Vec<BindingDescription>,
+    /// The errors found in the config file. If there are any, the default
+    /// config is used instead.
+    errors: Vec<ConfigError>,
 }
 
 impl Parser {
-    pub fn new() -> Self {
-        config_file_handler::null_check_config();
-        Self {
-            deserialized_config: Self::get_deserialized_config(),
+    /// Reads and parses the config file like `load()`, but uses the default
+    /// config if the file can't be read, with the reason in `errors()`.
+    pub fn new(path: Option<&std::path::Path>, layout_names: &[&str]) -> Self {
+        match Self::load(path, layout_names) {
+            Ok(parser) => parser,
+            Err(error) => {
+                let message: String = error
+                    .iter_chain()
+                    .map(|cause| cause.to_string())
+                    .collect::<Vec<String>>()
+                    .join(": ");
+                error!("{}, using the default config", message);
+                let mut parser: Parser = Self::default_config();
+                parser.errors = vec![ConfigError {
+                    position: None,
+                    message,
+                }];
+                parser
+            }
+        }
+    }
+
+    /// Reads and parses the config file at the given path, or else the one
+    /// in the XDG config directory, creating that first if it doesn't exist.
+    ///
+    /// Only failing to read the file is an error. If the file has errors in
+    /// it, they are collected and the default config is used instead. Each
+    /// workspace's layout must be one of the layout names.
+    pub fn load(path: Option<&std::path::Path>, layout_names: &[&str]) -> crate::Result<Self> {
+        let config_text: String = match path {
+            Some(path) => config_file_handler::read_config_file_at(path)?,
+            None => {
+                config_file_handler::null_check_config()?;
+                config_file_handler::read_config_file()?
+            }
+        };
+        Ok(Self::from_text(&config_text, layout_names))
+    }
+
+    /// Parses the text of a config file, falling back to the default config
+    /// if it has any errors.
+    pub fn from_text(config_text: &str, layout_names: &[&str]) -> Self {
+        match Self::lint(config_text, Some(layout_names)) {
+            Ok(parser) if parser.errors.is_empty() => parser,
+            Ok(Parser { errors, .. }) | Err(errors) => {
+                for error in errors.iter() {
+                    error!("Config error at {}", error);
+                }
+                error!(
+                    "Using the default config because of {} error(s) in the config file",
+                    errors.len()
+                );
+                let mut parser: Parser = Self::default_config();
+                parser.errors = errors;
+                parser
+            }
+        }
+    }
+
+    /// Parses the default config. Its layouts aren't checked, as they're the
+    /// ones the window manager comes with.
+    fn default_config() -> Self {
+        match Self::lint(config_file_handler::DEFAULT_CONFIG, None) {
+            Ok(parser) if parser.errors.is_empty() => parser,
+            _ => panic!("Invariant: The default config has errors!"),
         }
     }
 
+    /// Reads and parses a config file without falling back to the default
+    /// config, or creating the file if it doesn't exist.
+    pub fn lint_file(
+        path: &std::path::Path,
+        layout_names: &[&str],
+    ) -> crate::Result<Result<Self, Vec<ConfigError>>> {
+        let config_text: String = std::fs::read_to_string(path)
+            .with_context(|_| format!("Could not read config file {:?}", path))?;
+        Ok(Self::lint(&config_text, Some(layout_names)))
+    }
+
+    /// Returns the path the config file is read from, if it exists.
+    pub fn default_path() -> Option<std::path::PathBuf> {
+        config_file_handler::config_file_path()
+    }
+
+    /// Parses the text of a config file, keeping all of the bindings that
+    /// could be resolved alongside the errors in `errors()`. Fails only if the
+    /// file isn't valid YAML of the right shape.
+    ///
+    /// If layout names are given, each workspace's layout is checked to be
+    /// one of them.
+    fn lint(config_text: &str, layout_names: Option<&[&str]>) -> Result<Self, Vec<ConfigError>> {
+        let deserialized_config: config_deserializer::Config =
+            config_deserializer::deserialize_config(config_text)
+                .map_err(|error| vec![ConfigError::from_yaml(&error)])?;
+        let mut checker = Checker::new(config_text, &deserialized_config, layout_names);
+        let bindings: Bindings = checker.bindings();
+        let borders: Borders = checker.borders();
+        let log_rotation: LogRotation = checker.logging();
+        let autostart: Vec<Autostart> = checker.autostart();
+        checker.unknown_sections();
+        let Checker {
+            mut errors,
+            descriptions,
+            ..
+        } = checker;
+        errors.sort_by_key(|error| error.position.unwrap_or((usize::MAX, 0)));
+        Ok(Self {
+            deserialized_config,
+            bindings,
+            borders,
+            log_rotation,
+            autostart,
+            descriptions,
+            errors,
+        })
+    }
+
+    /// Returns the errors found in the config file, in the order they appear.
+    pub fn errors(&self) -> &[ConfigError] {
+        &self.errors
+    }
+
+    /// Returns every binding resolved from the config, in the order they
+    /// were checked.
+    pub fn describe_bindings(&self) -> &[BindingDescription] {
+        &self.descriptions
+    }
+
     pub fn get_gaps(&self) -> (Innergaps, Outergaps) {
         info!("Getting gap values");
         (
@@ -33,139 +265,942 @@ impl Parser {
         )
     }
 
+    pub fn get_focus_policy(&self) -> FocusPolicy {
+        info!("Getting focus policy");
+        self.deserialized_config.focus_policy
+    }
+
+    pub fn get_workspace_switching(&self) -> WorkspaceSwitching {
+        info!("Getting workspace switching");
+        self.deserialized_config.workspace_switching
+    }
+
+    pub fn get_borders(&self) -> Borders {
+        info!("Getting borders");
+        self.borders
+    }
+
+    pub fn get_log_rotation(&self) -> LogRotation {
+        info!("Getting log rotation");
+        self.log_rotation
+    }
+
+    pub fn get_autostart(&self) -> Vec<Autostart> {
+        info!("Getting autostart programs");
+        self.autostart.clone()
+    }
+
     pub fn get_bound_commands(&self) -> Vec<BoundCommand> {
         info!("Getting bound commands");
-        Self::get_parsed_bindings(self.deserialized_config.clone())
+        self.bindings.commands.clone()
+    }
+
+    /// Returns the bindings made of more than one chord, such as
+    /// `bind: Super+w h`.
+    pub fn get_bound_sequences(&self) -> Vec<BoundSequence> {
+        info!("Getting bound sequences");
+        self.bindings.sequences.clone()
+    }
+
+    /// Returns the bindings marked `on: release`, which run when their last
+    /// key is released.
+    pub fn get_bound_release_sequences(&self) -> Vec<BoundSequence> {
+        info!("Getting bound release sequences");
+        self.bindings.release_sequences.clone()
+    }
+
+    /// Returns the keymaps of the named modes.
+    pub fn get_bound_modes(&self) -> Vec<BoundMode> {
+        info!("Getting bound modes");
+        self.bindings.modes.clone()
+    }
+
+    pub fn get_bound_mouse_actions(&self) -> Vec<BoundMouseAction> {
+        info!("Getting mouse bindings");
+        self.bindings.mouse_actions.clone()
     }
 
     pub fn get_bound_workspaces(&self) -> Vec<BoundWorkSpace> {
         info!("Getting bound workspaces");
-        let mut result: Vec<BoundWorkSpace> = Vec::new();
-        let work_space_defs: Vec<HashMap<String, String>> =
-            self.deserialized_config.work_spaces.clone();
-        for work_space in work_space_defs {
-            if let Ok(parsed) = Self::parse_work_space(work_space.clone()) {
-                result.push(parsed);
-            } else {
-                error!("Could not parse workspace: {:?} continuing ...", work_space);
-                continue;
-            }
-        }
-        result
-    }
-
-    fn get_deserialized_config() -> config_deserializer::Config {
-        let config: String = config_file_handler::read_config_file();
-        config_deserializer::deserialize_config(config)
-    }
-
-    fn parse_work_space(work_space: HashMap<String, String>) -> Result<BoundWorkSpace, ()> {
-        let mask: ModKey = key_parse::parse_mask_keys(work_space["masks"].clone())[0];
-        let xk_key: u32 = key_parse::safe_xk_parse(&work_space["key"])?;
-        Ok((
-            mask,
-            xk_key,
-            work_space["name"].clone(),
-            work_space["layout"].clone(),
-        ))
-    }
-
-    fn get_parsed_bindings(deserialized_config: config_deserializer::Config) -> Vec<BoundCommand> {
-        let mut key_bindings: Vec<BoundCommand> =
-            Self::parse_keybindings_from_config(deserialized_config.key_bindings);
-        let spawn_bindings: Vec<BoundCommand> =
-            Self::parse_spawn_bindings_from_config(deserialized_config.spawn_bindings);
-        key_bindings.extend(spawn_bindings);
-        key_bindings
-    }
-
-    fn parse_keybindings_from_config(
-        key_bindings: Vec<HashMap<String, String>>,
-    ) -> Vec<BoundCommand> {
-        info!("Parsing keybindings");
-        let mut result: Vec<BoundCommand> = Vec::new();
-        for key_binding in key_bindings {
-            if let Ok(parsed_mask_and_key) = key_parse::parse_mask_and_key(
-                key_binding["masks"].clone(),
-                key_binding["key"].clone(),
-            ) {
-                let lazy_command: Command = lazy_commands::get_cmd_based_on_action(
-                    &lazy_commands::ActionTypes::from_str(&key_binding["function"]).unwrap(),
+        self.bindings.workspaces.clone()
+    }
+}
+
+/// A sequence of keys bound in a mode, or the usual keymap, either on press
+/// or on release.
+type KeyClaim = (Option<String>, Vec<(u32, XKeyValue)>, bool);
+
+/// An error in an entry of a list, naming the field it's in, if any.
+type EntryError = (Option<&'static str>, String);
+
+/// Turns the deserialized config into bindings, collecting every problem
+/// found along the way rather than stopping at the first.
+struct Checker<'a> {
+    config: &'a config_deserializer::Config,
+    locator: Locator<'a>,
+    errors: Vec<ConfigError>,
+    /// Where each sequence of keys was first bound, by the mode it's bound
+    /// in. Bindings on release are kept apart from those on press.
+    bound_keys: HashMap<KeyClaim, Option<Position>>,
+    descriptions: Vec<BindingDescription>,
+    /// The layouts workspaces may use, if they should be checked.
+    layout_names: Option<&'a [&'a str]>,
+}
+
+impl<'a> Checker<'a> {
+    fn new(
+        config_text: &'a str,
+        config: &'a config_deserializer::Config,
+        layout_names: Option<&'a [&'a str]>,
+    ) -> Checker<'a> {
+        Checker {
+            config,
+            locator: Locator::new(config_text),
+            errors: Vec::new(),
+            bound_keys: HashMap::new(),
+            descriptions: Vec::new(),
+            layout_names,
+        }
+    }
+
+    fn error<S: Into<String>>(&mut self, position: Option<Position>, message: S) {
+        self.errors.push(ConfigError {
+            position,
+            message: message.into(),
+        });
+    }
+
+    /// Records an error in an entry of the list under `path`.
+    fn entry_error(&mut self, path: &[&str], index: usize, (field, message): EntryError) {
+        let position: Option<Position> = match field {
+            Some(field) => self.locator.entry_field(path, index, field),
+            None => self.locator.entry(path, index),
+        };
+        self.error(position, message);
+    }
+
+    /// Records an entry which couldn't be deserialized, pointing at the
+    /// field responsible if the message names one.
+    fn invalid_entry(&mut self, path: &[&str], index: usize, message: &str) {
+        let position: Option<Position> = message
+            .strip_prefix("unknown field `")
+            .and_then(|rest| rest.split('`').next())
+            .and_then(|field| self.locator.entry_field(path, index, field))
+            .or_else(|| self.locator.entry(path, index));
+        self.error(position, message);
+    }
+
+    fn bindings(&mut self) -> Bindings {
+        let mut bindings = Bindings::default();
+        self.check_mod_key();
+        self.workspaces(&mut bindings);
+        self.key_bindings(&mut bindings);
+        self.spawn_bindings(&mut bindings);
+        self.modes(&mut bindings);
+        self.mouse_bindings(&mut bindings);
+        bindings
+    }
+
+    /// Returns the value of the `mod` variable, which defaults to Mod1.
+    fn mod_key(&self) -> &str {
+        self.config.mod_key.as_deref().unwrap_or("Mod1")
+    }
+
+    fn check_mod_key(&mut self) {
+        if ModKey::from_str(self.mod_key()).is_err() {
+            let message: String = format!("unknown modifier `{}` for $mod", self.mod_key());
+            let position: Option<Position> = self.locator.key(&["mod"]);
+            self.error(position, message);
+        }
+    }
+
+    /// Replaces references to the `mod` variable with its value.
+    fn expand_mod(&self, masks: &str) -> String {
+        masks.replace("$mod", self.mod_key())
+    }
+
+    /// Parses the keys of a binding, given either as `bind` chords such as
+    /// `Super+Shift+Return` or as separate `masks` and `key` fields.
+    fn chords(
+        &self,
+        bind: &Option<String>,
+        masks: &Option<String>,
+        key: &Option<String>,
+    ) -> Result<Vec<Chord>, EntryError> {
+        match (bind, key) {
+            (Some(bind), _) => {
+                key_parse::parse_sequence(&self.expand_mod(bind)).map_err(|e| (Some("bind"), e))
+            }
+            (None, Some(key)) => {
+                let masks: String = self.expand_mod(masks.as_deref().unwrap_or_default());
+                key_parse::parse_mask_keys(&masks)
+                    .map_err(|e| (Some("masks"), e))
+                    .and_then(|mods| {
+                        let key = key_parse::safe_xk_parse(key).map_err(|e| (Some("key"), e))?;
+                        Ok(vec![(mods, key)])
+                    })
+            }
+            (None, None) => Err((None, "missing `bind`, or `key` and `masks`".to_string())),
+        }
+    }
+
+    /// Checks that a sequence of keys isn't already bound in the same mode,
+    /// and records what it's bound to.
+    fn claim_keys(
+        &mut self,
+        mode: Option<&str>,
+        chords: &[Chord],
+        release: bool,
+        position: Option<Position>,
+        action: String,
+    ) -> Result<(), EntryError> {
+        let keys: Vec<(u32, XKeyValue)> = chords
+            .iter()
+            .map(|(mods, key)| (ModKey::combine(mods), *key))
+            .collect();
+        let claim: KeyClaim = (mode.map(String::from), keys, release);
+        match self.bound_keys.get(&claim) {
+            Some(Some((line, _))) => Err((
+                None,
+                format!(
+                    "duplicate binding, these keys are already bound on line {}",
+                    line
+                ),
+            )),
+            Some(None) => Err((None, "duplicate binding".to_string())),
+            None => {
+                self.bound_keys.insert(claim, position);
+                self.descriptions.push(BindingDescription {
+                    mode: mode.map(String::from),
+                    keys: describe_chords(chords),
+                    on_release: release,
+                    action,
+                });
+                Ok(())
+            }
+        }
+    }
+
+    /// Builds the command run by a function, where `EnterMode` takes the name
+    /// of a mode.
+    fn function_command(
+        &self,
+        function: &str,
+        mode: &Option<String>,
+    ) -> Result<Command, EntryError> {
+        if function == "EnterMode" {
+            let mode: &String = mode
+                .as_ref()
+                .ok_or((Some("function"), "EnterMode needs a `mode`".to_string()))?;
+            if !self.config.modes.contains_key(mode) {
+                return Err((Some("mode"), format!("unknown mode `{}`", mode)));
+            }
+            return Ok(lazy_commands::enter_mode(mode.clone()));
+        }
+        let action = lazy_commands::ActionTypes::from_str(function)
+            .map_err(|_| (Some("function"), format!("unknown function `{}`", function)))?;
+        Ok(lazy_commands::get_cmd_based_on_action(&action))
+    }
+
+    fn spawn_command(command: &str, args: &Option<String>) -> Command {
+        lazy_commands::lazy_spawn(
+            command.to_string(),
+            split_args(args.as_deref().unwrap_or_default()),
+        )
+    }
+
+    /// Files a binding under single chords, sequences or releases.
+    fn add_binding(
+        &mut self,
+        bindings: &mut Bindings,
+        path: &[&str],
+        index: usize,
+        chords: Vec<Chord>,
+        on: Trigger,
+        (command, action): (Command, String),
+    ) -> Result<(), EntryError> {
+        let release: bool = on == Trigger::Release;
+        let position: Option<Position> = self.locator.entry(path, index);
+        self.claim_keys(None, &chords, release, position, action)?;
+        if release {
+            bindings.release_sequences.push((chords, command));
+        } else if chords.len() == 1 {
+            let (mods, key) = chords.into_iter().next().unwrap();
+            bindings.commands.push((mods, key, command));
+        } else {
+            bindings.sequences.push((chords, command));
+        }
+        Ok(())
+    }
+
+    fn key_bindings(&mut self, bindings: &mut Bindings) {
+        let path: &[&str] = &["key_bindings"];
+        let config: &config_deserializer::Config = self.config;
+        for (index, entry) in config.key_bindings.iter().enumerate() {
+            let result: Result<(), EntryError> = match entry {
+                Entry::Invalid(message) => {
+                    self.invalid_entry(path, index, message);
+                    continue;
+                }
+                Entry::Valid(binding) => self
+                    .chords(&binding.bind, &binding.masks, &binding.key)
+                    .and_then(|chords| {
+                        let command: Command =
+                            self.function_command(&binding.function, &binding.mode)?;
+                        let action: String = describe_function(&binding.function, &binding.mode);
+                        self.add_binding(
+                            bindings,
+                            path,
+                            index,
+                            chords,
+                            binding.on,
+                            (command, action),
+                        )
+                    }),
+            };
+            if let Err(error) = result {
+                self.entry_error(path, index, error);
+            }
+        }
+    }
+
+    fn spawn_bindings(&mut self, bindings: &mut Bindings) {
+        let path: &[&str] = &["spawn_bindings"];
+        let config: &config_deserializer::Config = self.config;
+        for (index, entry) in config.spawn_bindings.iter().enumerate() {
+            let result: Result<(), EntryError> = match entry {
+                Entry::Invalid(message) => {
+                    self.invalid_entry(path, index, message);
+                    continue;
+                }
+                Entry::Valid(binding) => self
+                    .chords(&binding.bind, &binding.masks, &binding.key)
+                    .and_then(|chords| {
+                        let command: Command = Self::spawn_command(&binding.command, &binding.args);
+                        let action: String = describe_spawn(&binding.command, &binding.args);
+                        self.add_binding(
+                            bindings,
+                            path,
+                            index,
+                            chords,
+                            binding.on,
+                            (command, action),
+                        )
+                    }),
+            };
+            if let Err(error) = result {
+                self.entry_error(path, index, error);
+            }
+        }
+    }
+
+    /// Within a mode, a binding either runs a function or spawns a command.
+    fn modes(&mut self, bindings: &mut Bindings) {
+        let config: &config_deserializer::Config = self.config;
+        // Whether each mode has a binding to ExitMode, and the modes it
+        // enters.
+        let mut exits_by_mode: HashMap<&String, (bool, Vec<&String>)> = HashMap::new();
+        for (name, entries) in config.modes.iter() {
+            let path: &[&str] = &["modes", name.as_str()];
+            let mut mode_bindings: Vec<BoundSequence> = Vec::new();
+            let mut exits: bool = false;
+            let mut enters: Vec<&String> = Vec::new();
+            for (index, entry) in entries.iter().enumerate() {
+                let result: Result<(), EntryError> = match entry {
+                    Entry::Invalid(message) => {
+                        self.invalid_entry(path, index, message);
+                        continue;
+                    }
+                    Entry::Valid(binding) => self
+                        .chords(&binding.bind, &binding.masks, &binding.key)
+                        .and_then(|chords| {
+                            let (command, action): (Command, String) =
+                                match (&binding.function, &binding.command) {
+                                    (Some(function), None) => (
+                                        self.function_command(function, &binding.mode)?,
+                                        describe_function(function, &binding.mode),
+                                    ),
+                                    (None, Some(command)) => (
+                                        Self::spawn_command(command, &binding.args),
+                                        describe_spawn(command, &binding.args),
+                                    ),
+                                    _ => {
+                                        return Err((
+                                            None,
+                                            "needs either a `function` or a `command`".to_string(),
+                                        ))
+                                    }
+                                };
+                            let position: Option<Position> = self.locator.entry(path, index);
+                            self.claim_keys(Some(name), &chords, false, position, action)?;
+                            mode_bindings.push((chords, command));
+                            match (binding.function.as_deref(), &binding.mode) {
+                                (Some("ExitMode"), _) => exits = true,
+                                (Some("EnterMode"), Some(mode)) => enters.push(mode),
+                                _ => {}
+                            }
+                            Ok(())
+                        }),
+                };
+                if let Err(error) = result {
+                    self.entry_error(path, index, error);
+                }
+            }
+            exits_by_mode.insert(name, (exits, enters));
+            bindings.modes.push((name.clone(), mode_bindings));
+        }
+
+        // The keyboard stays grabbed while a mode is active, so each mode
+        // must lead back to the usual keymap, perhaps through other modes.
+        let mut can_exit: HashSet<&String> = HashSet::new();
+        loop {
+            let before: usize = can_exit.len();
+            for (name, (exits, enters)) in exits_by_mode.iter() {
+                if *exits || enters.iter().any(|mode| can_exit.contains(mode)) {
+                    can_exit.insert(name);
+                }
+            }
+            if can_exit.len() == before {
+                break;
+            }
+        }
+        for name in config.modes.keys() {
+            if !can_exit.contains(name) {
+                let position: Option<Position> = self.locator.key(&["modes", name.as_str()]);
+                self.error(
+                    position,
+                    format!(
+                        "mode `{}` has no way back to the usual keys, bind ExitMode in it",
+                        name
+                    ),
                 );
-                result.push((parsed_mask_and_key.0, parsed_mask_and_key.1, lazy_command));
-            } else {
-                error!("Could not parse {:?} continuing ...", key_binding);
-                continue;
             }
         }
-        result
     }
 
-    fn parse_spawn_bindings_from_config(
-        spawn_bindings: Vec<HashMap<String, String>>,
-    ) -> Vec<BoundCommand> {
-        info!("Parsing spawn bindings");
-        let mut result: Vec<BoundCommand> = Vec::new();
-        for spawn_kb in spawn_bindings {
-            if let Ok(parsed_mask_and_key) =
-                key_parse::parse_mask_and_key(spawn_kb["masks"].clone(), spawn_kb["key"].clone())
-            {
-                let lazy_command: Command = Self::get_lazy_spawn_command(
-                    spawn_kb["command"].clone(),
-                    spawn_kb["args"].clone(),
+    /// Workspaces are switched to with a single mod, and have windows moved
+    /// to them with Shift added.
+    fn workspaces(&mut self, bindings: &mut Bindings) {
+        let path: &[&str] = &["work_spaces"];
+        let config: &config_deserializer::Config = self.config;
+        for (index, entry) in config.work_spaces.iter().enumerate() {
+            let result: Result<(), EntryError> = match entry {
+                Entry::Invalid(message) => {
+                    self.invalid_entry(path, index, message);
+                    continue;
+                }
+                Entry::Valid(work_space) => self
+                    .chords(&work_space.bind, &work_space.masks, &work_space.key)
+                    .and_then(|chords| {
+                        let (mods, key): Chord = match chords.as_slice() {
+                            [chord] => chord.clone(),
+                            _ => {
+                                return Err((
+                                    Some("bind"),
+                                    "workspaces are bound to a single chord".to_string(),
+                                ))
+                            }
+                        };
+                        let mask: ModKey = *mods.first().ok_or((
+                            None,
+                            "workspaces need a modifier to switch to them with".to_string(),
+                        ))?;
+                        if bindings.workspaces.iter().any(|w| w.2 == work_space.name) {
+                            return Err((
+                                Some("name"),
+                                format!("duplicate workspace `{}`", work_space.name),
+                            ));
+                        }
+                        if let Some(layout_names) = self.layout_names {
+                            if !layout_names.contains(&work_space.layout.as_str()) {
+                                return Err((
+                                    Some("layout"),
+                                    format!(
+                                        "unknown layout `{}`, expected one of: {}",
+                                        work_space.layout,
+                                        layout_names.join(", ")
+                                    ),
+                                ));
+                            }
+                        }
+                        let position: Option<Position> = self.locator.entry(path, index);
+                        self.claim_keys(
+                            None,
+                            &[(vec![mask], key)],
+                            false,
+                            position,
+                            format!("switch to workspace {}", work_space.name),
+                        )?;
+                        self.claim_keys(
+                            None,
+                            &[(vec![mask, ModKey::Shift], key)],
+                            false,
+                            position,
+                            format!("move window to workspace {}", work_space.name),
+                        )?;
+                        bindings.workspaces.push((
+                            mask,
+                            key,
+                            work_space.name.clone(),
+                            work_space.layout.clone(),
+                        ));
+                        Ok(())
+                    }),
+            };
+            if let Err(error) = result {
+                self.entry_error(path, index, error);
+            }
+        }
+    }
+
+    fn mouse_bindings(&mut self, bindings: &mut Bindings) {
+        let path: &[&str] = &["mouse_bindings"];
+        let config: &config_deserializer::Config = self.config;
+        for (index, entry) in config.mouse_bindings.iter().enumerate() {
+            let result: Result<BoundMouseAction, EntryError> = match entry {
+                Entry::Invalid(message) => {
+                    self.invalid_entry(path, index, message);
+                    continue;
+                }
+                Entry::Valid(binding) => self.mouse_binding(binding),
+            };
+            match result {
+                Ok(parsed) => {
+                    let combo = (ModKey::combine(&parsed.0), parsed.1);
+                    if bindings
+                        .mouse_actions
+                        .iter()
+                        .any(|(mods, button, _)| (ModKey::combine(mods), *button) == combo)
+                    {
+                        self.entry_error(path, index, (None, "duplicate binding".to_string()));
+                    } else {
+                        self.descriptions.push(BindingDescription {
+                            mode: None,
+                            keys: describe_mods(&parsed.0, format!("Button{}", parsed.1)),
+                            on_release: false,
+                            action: format!("{:?} window", parsed.2),
+                        });
+                        bindings.mouse_actions.push(parsed);
+                    }
+                }
+                Err(error) => self.entry_error(path, index, error),
+            }
+        }
+    }
+
+    fn mouse_binding(
+        &self,
+        binding: &config_deserializer::MouseBinding,
+    ) -> Result<BoundMouseAction, EntryError> {
+        let masks: String = self.expand_mod(binding.masks.as_deref().unwrap_or_default());
+        let masks: Vec<ModKey> =
+            key_parse::parse_mask_keys(&masks).map_err(|e| (Some("masks"), e))?;
+        let button: Button =
+            key_parse::parse_button(&binding.button).map_err(|e| (Some("button"), e))?;
+        let action: MouseAction = MouseAction::from_str(&binding.action).map_err(|_| {
+            (
+                Some("action"),
+                format!("unknown mouse action `{}`", binding.action),
+            )
+        })?;
+        Ok((masks, button, action))
+    }
+
+    /// Parses the border colours, where any left out use the default.
+    fn borders(&mut self) -> Borders {
+        let borders: &config_deserializer::Borders = &self.config.borders;
+        let defaults: Borders = Borders::default();
+        Borders {
+            width: borders.width.unwrap_or(defaults.width),
+            focused: self.colour("focused", &borders.focused, defaults.focused),
+            unfocused: self.colour("unfocused", &borders.unfocused, defaults.unfocused),
+            urgent: self.colour("urgent", &borders.urgent, defaults.urgent),
+            floating: self.colour("floating", &borders.floating, defaults.floating),
+        }
+    }
+
+    /// Parses a `#rrggbb` colour.
+    fn colour(&mut self, name: &str, colour: &Option<String>, default: u32) -> u32 {
+        let colour: &str = match colour {
+            Some(colour) => colour,
+            None => return default,
+        };
+        match u32::from_str_radix(colour.trim_start_matches('#'), 16) {
+            Ok(pixel) if colour.len() == 7 && colour.starts_with('#') => pixel,
+            _ => {
+                let position: Option<Position> = self.locator.key(&["borders", name]);
+                self.error(
+                    position,
+                    format!("colour `{}` is not of the form '#rrggbb'", colour),
                 );
-                result.push((parsed_mask_and_key.0, parsed_mask_and_key.1, lazy_command));
-            } else {
-                error!("Could not parse {:?} continuing", spawn_kb);
-                continue;
+                default
             }
         }
-        result
     }
 
-    fn get_lazy_spawn_command(command: String, pipe_separated_args: String) -> Command {
-        lazy_commands::lazy_spawn(command, Self::split_args(pipe_separated_args))
+    /// Parses how the log is rotated, where anything left out uses the
+    /// default.
+    fn logging(&mut self) -> LogRotation {
+        let logging: &config_deserializer::Logging = &self.config.logging;
+        let defaults: LogRotation = LogRotation::default();
+        let max_size: u64 = match logging.max_size {
+            Some(ref size) => match parse_size(size) {
+                Ok(size) => size,
+                Err(message) => {
+                    let position: Option<Position> = self.locator.key(&["logging", "max_size"]);
+                    self.error(position, message);
+                    defaults.max_size
+                }
+            },
+            None => defaults.max_size,
+        };
+        LogRotation {
+            rotate: logging.rotate.unwrap_or(defaults.rotate),
+            max_size,
+            keep: logging.keep.unwrap_or(defaults.keep),
+            compress: logging.compress.unwrap_or(defaults.compress),
+        }
     }
 
-    fn split_args(pipe_separated_args: String) -> Vec<String> {
-        pipe_separated_args
-            .split("|")
-            .map(|i| {
-                if i == "~" {
-                    "".to_string()
-                } else {
-                    i.to_string()
+    fn autostart(&mut self) -> Vec<Autostart> {
+        let path: &[&str] = &["autostart"];
+        let config: &config_deserializer::Config = self.config;
+        let mut programs: Vec<Autostart> = Vec::new();
+        for (index, entry) in config.autostart.iter().enumerate() {
+            let result: Result<Autostart, EntryError> = match entry {
+                Entry::Invalid(message) => {
+                    self.invalid_entry(path, index, message);
+                    continue;
                 }
-            })
-            .collect()
+                Entry::Valid(program) => Self::autostart_program(config, program),
+            };
+            match result {
+                Ok(program) => programs.push(program),
+                Err(error) => self.entry_error(path, index, error),
+            }
+        }
+        programs
+    }
+
+    fn autostart_program(
+        config: &config_deserializer::Config,
+        program: &config_deserializer::Autostart,
+    ) -> Result<Autostart, EntryError> {
+        let (command, once): (&String, bool) = match (&program.exec, &program.exec_once) {
+            (Some(command), None) => (command, false),
+            (None, Some(command)) => (command, true),
+            _ => {
+                return Err((
+                    None,
+                    "autostart entries need one of `exec` or `exec_once`".to_string(),
+                ))
+            }
+        };
+        if let Some(ref workspace) = program.workspace {
+            let known: bool = config.work_spaces.iter().any(
+                |entry| matches!(entry, Entry::Valid(work_space) if &work_space.name == workspace),
+            );
+            if !known {
+                return Err((
+                    Some("workspace"),
+                    format!("unknown workspace `{}`", workspace),
+                ));
+            }
+        }
+        let args: Vec<String> = match program.args {
+            Some(ref args) if !args.is_empty() => split_args(args),
+            _ => Vec::new(),
+        };
+        Ok(Autostart {
+            command: command.clone(),
+            args,
+            once,
+            workspace: program.workspace.clone(),
+        })
+    }
+
+    fn unknown_sections(&mut self) {
+        for name in self.config.unknown.keys() {
+            let position: Option<Position> = self.locator.key(&[name]);
+            self.error(position, format!("unknown section `{}`", name));
+        }
+    }
+}
+
+/// Joins mods and a key or button with `+`, as they'd be written in a chord.
+fn describe_mods(mods: &[ModKey], key: String) -> String {
+    mods.iter()
+        .map(|mod_key| format!("{:?}", mod_key))
+        .chain(std::iter::once(key))
+        .collect::<Vec<String>>()
+        .join("+")
+}
+
+fn describe_chords(chords: &[Chord]) -> String {
+    chords
+        .iter()
+        .map(|(mods, key)| describe_mods(mods, key_parse::keysym_name(*key)))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn describe_function(function: &str, mode: &Option<String>) -> String {
+    match mode {
+        Some(mode) if function == "EnterMode" => format!("{} {}", function, mode),
+        _ => function.to_string(),
+    }
+}
+
+fn describe_spawn(command: &str, args: &Option<String>) -> String {
+    let args: Vec<String> = split_args(args.as_deref().unwrap_or_default());
+    format!("spawn {} {}", command, args.join(" "))
+        .trim_end()
+        .to_string()
+}
+
+/// Parses a number of bytes, which may end in `K`, `M` or `G`.
+fn parse_size(size: &str) -> Result<u64, String> {
+    let size: &str = size.trim();
+    let (number, unit): (&str, u64) = match size.chars().last() {
+        Some('K') | Some('k') => (&size[..size.len() - 1], 1024),
+        Some('M') | Some('m') => (&size[..size.len() - 1], 1024 * 1024),
+        Some('G') | Some('g') => (&size[..size.len() - 1], 1024 * 1024 * 1024),
+        _ => (size, 1),
+    };
+    match number.trim().parse::<u64>() {
+        Ok(number) if number > 0 => Ok(number * unit),
+        _ => Err(format!(
+            "size `{}` is not a number of bytes, such as 500K or 10M",
+            size
+        )),
     }
 }
 
+fn split_args(pipe_separated_args: &str) -> Vec<String> {
+    pipe_separated_args
+        .split("|")
+        .map(|i| {
+            if i == "~" {
+                "".to_string()
+            } else {
+                i.to_string()
+            }
+        })
+        .collect()
+}
+
 mod config_deserializer {
+    use crate::settings::{FocusPolicy, Rotate, WorkspaceSwitching};
+    use serde::de::{DeserializeOwned, Deserializer};
     use serde::Deserialize;
-    use std::collections::HashMap;
+    use serde_yaml::Value;
+    use std::collections::BTreeMap;
 
     #[derive(Deserialize, Debug, Clone)]
     pub struct Config {
-        pub key_bindings: Vec<HashMap<String, String>>,
-        pub spawn_bindings: Vec<HashMap<String, String>>,
-        pub work_spaces: Vec<HashMap<String, String>>,
+        #[serde(default)]
+        pub key_bindings: Vec<Entry<KeyBinding>>,
+        #[serde(default)]
+        pub spawn_bindings: Vec<Entry<SpawnBinding>>,
+        #[serde(default)]
+        pub work_spaces: Vec<Entry<WorkSpace>>,
+        #[serde(default)]
+        pub mouse_bindings: Vec<Entry<MouseBinding>>,
+        /// Keymaps which replace the usual one while their mode is active.
+        #[serde(default)]
+        pub modes: BTreeMap<String, Vec<Entry<ModeBinding>>>,
+        #[serde(default)]
         pub gaps: Gaps,
+        #[serde(default)]
+        pub focus_policy: FocusPolicy,
+        #[serde(default)]
+        pub workspace_switching: WorkspaceSwitching,
+        #[serde(default)]
+        pub borders: Borders,
+        #[serde(default)]
+        pub logging: Logging,
+        /// Programs to start with the window manager.
+        #[serde(default)]
+        pub autostart: Vec<Entry<Autostart>>,
+        /// The mod substituted for `$mod` in bindings.
+        #[serde(default, rename = "mod")]
+        pub mod_key: Option<String>,
+        /// Anything else, which is reported rather than silently ignored.
+        #[serde(flatten)]
+        pub unknown: BTreeMap<String, Value>,
+    }
+
+    /// An entry of a list, checked on its own so that one bad entry doesn't
+    /// stop the others from being checked.
+    #[derive(Debug, Clone)]
+    pub enum Entry<T> {
+        Valid(T),
+        Invalid(String),
+    }
+
+    impl<'de, T: DeserializeOwned> Deserialize<'de> for Entry<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let value: Value = Value::deserialize(deserializer)?;
+            Ok(match serde_yaml::from_value(value) {
+                Ok(entry) => Entry::Valid(entry),
+                Err(error) => Entry::Invalid(error.to_string()),
+            })
+        }
+    }
+
+    /// When a key binding runs.
+    #[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Trigger {
+        #[default]
+        Press,
+        Release,
+    }
+
+    #[derive(Deserialize, Debug, Clone)]
+    #[serde(deny_unknown_fields)]
+    pub struct KeyBinding {
+        pub function: String,
+        /// The mode entered by `EnterMode`.
+        pub mode: Option<String>,
+        #[serde(default, deserialize_with = "optional_scalar")]
+        pub bind: Option<String>,
+        pub masks: Option<String>,
+        #[serde(default, deserialize_with = "optional_scalar")]
+        pub key: Option<String>,
+        #[serde(default)]
+        pub on: Trigger,
+    }
+
+    #[derive(Deserialize, Debug, Clone)]
+    #[serde(deny_unknown_fields)]
+    pub struct SpawnBinding {
+        pub command: String,
+        /// Arguments separated by pipe symbols.
+        #[serde(default, deserialize_with = "optional_scalar")]
+        pub args: Option<String>,
+        #[serde(default, deserialize_with = "optional_scalar")]
+        pub bind: Option<String>,
+        pub masks: Option<String>,
+        #[serde(default, deserialize_with = "optional_scalar")]
+        pub key: Option<String>,
+        #[serde(default)]
+        pub on: Trigger,
+    }
+
+    /// Either a function or a spawn binding.
+    #[derive(Deserialize, Debug, Clone)]
+    #[serde(deny_unknown_fields)]
+    pub struct ModeBinding {
+        pub function: Option<String>,
+        pub mode: Option<String>,
+        pub command: Option<String>,
+        #[serde(default, deserialize_with = "optional_scalar")]
+        pub args: Option<String>,
+        #[serde(default, deserialize_with = "optional_scalar")]
+        pub bind: Option<String>,
+        pub masks: Option<String>,
+        #[serde(default, deserialize_with = "optional_scalar")]
+        pub key: Option<String>,
+    }
+
+    #[derive(Deserialize, Debug, Clone)]
+    #[serde(deny_unknown_fields)]
+    pub struct WorkSpace {
+        #[serde(deserialize_with = "scalar")]
+        pub name: String,
+        pub layout: String,
+        #[serde(default, deserialize_with = "optional_scalar")]
+        pub bind: Option<String>,
+        pub masks: Option<String>,
+        #[serde(default, deserialize_with = "optional_scalar")]
+        pub key: Option<String>,
+    }
+
+    #[derive(Deserialize, Debug, Clone)]
+    #[serde(deny_unknown_fields)]
+    pub struct MouseBinding {
+        pub action: String,
+        pub masks: Option<String>,
+        #[serde(deserialize_with = "scalar")]
+        pub button: String,
+    }
+
+    /// Any of these may be left out to use the default.
+    #[derive(Deserialize, Debug, Clone, Default)]
+    #[serde(deny_unknown_fields)]
+    pub struct Borders {
+        pub width: Option<u32>,
+        pub focused: Option<String>,
+        pub unfocused: Option<String>,
+        pub urgent: Option<String>,
+        pub floating: Option<String>,
     }
 
+    /// Has one of `exec` or `exec_once`.
     #[derive(Deserialize, Debug, Clone)]
+    #[serde(deny_unknown_fields)]
+    pub struct Autostart {
+        pub exec: Option<String>,
+        pub exec_once: Option<String>,
+        /// Arguments separated by pipe symbols.
+        #[serde(default, deserialize_with = "optional_scalar")]
+        pub args: Option<String>,
+        #[serde(default, deserialize_with = "optional_scalar")]
+        pub workspace: Option<String>,
+    }
+
+    /// Any of these may be left out to use the default.
+    #[derive(Deserialize, Debug, Clone, Default)]
+    #[serde(deny_unknown_fields)]
+    pub struct Logging {
+        pub rotate: Option<Rotate>,
+        #[serde(default, deserialize_with = "optional_scalar")]
+        pub max_size: Option<String>,
+        pub keep: Option<usize>,
+        pub compress: Option<bool>,
+    }
+
+    #[derive(Deserialize, Debug, Clone, Default)]
+    #[serde(deny_unknown_fields)]
     pub struct Gaps {
+        #[serde(default)]
         pub inner: u32,
+        #[serde(default)]
         pub outer: u32,
     }
 
-    pub fn deserialize_config(config_file: String) -> Config {
+    /// Reads a string, number or boolean as a string, so that e.g. workspaces
+    /// can be named `1` and buttons given as `1`.
+    fn scalar<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+        match Value::deserialize(deserializer)? {
+            Value::String(string) => Ok(string),
+            Value::Number(number) => Ok(number.to_string()),
+            Value::Bool(boolean) => Ok(boolean.to_string()),
+            _ => Err(serde::de::Error::custom("expected a string")),
+        }
+    }
+
+    /// Like `scalar()`, but a missing or empty value is `None`.
+    fn optional_scalar<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<String>, D::Error> {
+        match Value::deserialize(deserializer)? {
+            Value::Null => Ok(None),
+            Value::String(string) => Ok(Some(string)),
+            Value::Number(number) => Ok(Some(number.to_string())),
+            Value::Bool(boolean) => Ok(Some(boolean.to_string())),
+            _ => Err(serde::de::Error::custom("expected a string")),
+        }
+    }
+
+    pub fn deserialize_config(config_file: &str) -> Result<Config, serde_yaml::Error> {
         info!("Deserializing config");
-        serde_yaml::from_str(&config_file).expect("Could not parse config file")
+        serde_yaml::from_str(config_file)
     }
 }
 
-mod lazy_commands {
+pub(crate) mod lazy_commands {
     use crate::cmd::{self, Command};
 
     #[derive(EnumString)]
@@ -180,6 +1215,14 @@ mod lazy_commands {
         DecreaseInnerGaps,
         IncreaseOuterGaps,
         DecreaseOuterGaps,
+        ToggleFloating,
+        ToggleFullscreen,
+        FocusMonitorNext,
+        FocusMonitorPrev,
+        MoveWindowToMonitor,
+        SwapWorkspacesBetweenMonitors,
+        ExitMode,
+        ReloadConfig,
     }
 
     pub fn get_cmd_based_on_action(action: &ActionTypes) -> Command {
@@ -194,10 +1237,216 @@ mod lazy_commands {
             ActionTypes::DecreaseInnerGaps => cmd::lazy::decrease_innergaps(),
             ActionTypes::IncreaseOuterGaps => cmd::lazy::increase_outergaps(),
             ActionTypes::DecreaseOuterGaps => cmd::lazy::decrease_outergaps(),
+            ActionTypes::ToggleFloating => cmd::lazy::toggle_floating(),
+            ActionTypes::ToggleFullscreen => cmd::lazy::toggle_fullscreen(),
+            ActionTypes::FocusMonitorNext => cmd::lazy::focus_monitor_next(),
+            ActionTypes::FocusMonitorPrev => cmd::lazy::focus_monitor_previous(),
+            ActionTypes::MoveWindowToMonitor => cmd::lazy::move_window_to_monitor(),
+            ActionTypes::SwapWorkspacesBetweenMonitors => {
+                cmd::lazy::swap_workspaces_between_monitors()
+            }
+            ActionTypes::ExitMode => cmd::lazy::exit_mode(),
+            ActionTypes::ReloadConfig => cmd::lazy::reload_config(),
         }
     }
 
+    pub fn enter_mode(mode: String) -> Command {
+        cmd::lazy::enter_mode(mode)
+    }
+
     pub fn lazy_spawn(command: String, args: Vec<String>) -> Command {
         cmd::lazy::spawn(command, args)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::config_file_handler::DEFAULT_CONFIG;
+    use super::{parse_size, Parser};
+    use std::path::{Path, PathBuf};
+
+    const LAYOUTS: &[&str] = &["tile", "c_master"];
+
+    /// Lints a config, returning the errors with their positions.
+    fn errors(config_text: &str) -> Vec<(Option<(usize, usize)>, String)> {
+        let parser: Parser = match Parser::lint(config_text, Some(LAYOUTS)) {
+            Ok(parser) => parser,
+            Err(errors) => panic!("Config didn't parse: {:?}", errors),
+        };
+        parser
+            .errors()
+            .iter()
+            .map(|error| (error.position, error.message.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_default_config_is_clean() {
+        let parser: Parser = match Parser::lint(DEFAULT_CONFIG, Some(LAYOUTS)) {
+            Ok(parser) => parser,
+            Err(errors) => panic!("Default config didn't parse: {:?}", errors),
+        };
+        assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+        assert!(!parser.describe_bindings().is_empty());
+    }
+
+    #[test]
+    fn test_unknown_field() {
+        let config: &str = "\
+key_bindings:
+  - {function: FocusNext, bind: Mod1+j}
+  - {function: FocusPrev, bnid: Mod1+k}
+";
+        let errors = errors(config);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, Some((3, 27)));
+        assert!(errors[0].1.starts_with("unknown field `bnid`"));
+    }
+
+    #[test]
+    fn test_bad_key() {
+        let config: &str = "\
+spawn_bindings:
+  - command: xterm
+    masks: Mod1
+    key: XK_Retrun
+";
+        let errors = errors(config);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, Some((4, 5)));
+        assert_eq!(errors[0].1, "unknown key `XK_Retrun`");
+    }
+
+    #[test]
+    fn test_duplicate_binding() {
+        let config: &str = "\
+key_bindings:
+  - {function: FocusNext, bind: Mod1+j}
+
+spawn_bindings:
+  - command: xterm
+    bind: Alt+j
+";
+        let errors = errors(config);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, Some((5, 3)));
+        assert!(errors[0].1.contains("already bound"), "{}", errors[0].1);
+        assert!(errors[0].1.contains("line 2"), "{}", errors[0].1);
+    }
+
+    #[test]
+    fn test_unknown_section() {
+        let config: &str = "\
+gaps:
+  inner: 4
+keybindings:
+  - {function: FocusNext, bind: Mod1+j}
+";
+        let errors = errors(config);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, Some((3, 1)));
+        assert_eq!(errors[0].1, "unknown section `keybindings`");
+    }
+
+    #[test]
+    fn test_mode_without_exit() {
+        let config: &str = "\
+key_bindings:
+  - {function: EnterMode, mode: resize, bind: Mod1+r}
+modes:
+  resize:
+    - {function: IncreaseMaster, bind: l}
+  move:
+    - {function: EnterMode, mode: resize, bind: r}
+";
+        let found = errors(config);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].0, Some((4, 3)));
+        assert!(found[0].1.starts_with("mode `resize` has no way back"));
+        assert_eq!(found[1].0, Some((6, 3)));
+        assert!(found[1].1.starts_with("mode `move` has no way back"));
+
+        // Modes can be left through another mode.
+        let config: &str = "\
+modes:
+  resize:
+    - {function: EnterMode, mode: move, bind: m}
+  move:
+    - {function: EnterMode, mode: resize, bind: r}
+    - {function: ExitMode, bind: Escape}
+";
+        assert!(errors(config).is_empty());
+    }
+
+    #[test]
+    fn test_flow_and_block_entries() {
+        // The same bad entry, third in the list, written either way.
+        let flow: &str = "\
+key_bindings:
+  - {function: FocusNext, bind: Mod1+j}
+  - {function: FocusPrev, bind: Mod1+k}
+  - {function: Nope, bind: Mod1+l}
+";
+        let block: &str = "\
+key_bindings:
+  - function: FocusNext
+    bind: Mod1+j
+  - function: FocusPrev
+    bind: Mod1+k
+
+  # A comment.
+  - bind: Mod1+l
+    function: Nope
+";
+        assert_eq!(errors(flow)[0].0, Some((4, 6)));
+        assert_eq!(errors(block)[0].0, Some((9, 5)));
+        assert_eq!(errors(flow)[0].1, errors(block)[0].1);
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size("500K"), Ok(500 * 1024));
+        assert_eq!(parse_size("10M"), Ok(10 * 1024 * 1024));
+        assert_eq!(parse_size(" 2g "), Ok(2 * 1024 * 1024 * 1024));
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("10MB").is_err());
+        assert!(parse_size("-1K").is_err());
+    }
+
+    #[test]
+    fn test_missing_config_file_uses_default() {
+        let parser: Parser =
+            Parser::new(Some(Path::new("/nonexistent/volan/config.yaml")), LAYOUTS);
+        assert_eq!(parser.errors().len(), 1);
+        assert!(parser.errors()[0]
+            .message
+            .contains("Could not open config file"));
+        assert!(!parser.get_bound_commands().is_empty());
+    }
+
+    #[test]
+    fn test_unknown_layout_uses_default() {
+        let parser: Parser = Parser::from_text(
+            "work_spaces:\n  - {name: 1, layout: spiral, bind: Mod1+1}\n",
+            LAYOUTS,
+        );
+        assert_eq!(parser.errors().len(), 1);
+        assert!(parser.errors()[0]
+            .message
+            .contains("unknown layout `spiral`"));
+        assert!(parser.get_bound_workspaces().len() > 1);
+    }
+
+    #[test]
+    fn test_unreadable_config_file_uses_default() {
+        let path: PathBuf =
+            std::env::temp_dir().join(format!("volan-test-{}.yaml", std::process::id()));
+        std::fs::write(&path, b"gaps:\n  inner: \xff\n").unwrap();
+        let parser: Parser = Parser::new(Some(&path), LAYOUTS);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(parser.errors().len(), 1);
+        assert!(!parser.get_bound_commands().is_empty());
+    }
+}
@@ -1,45 +1,76 @@
+use failure::{format_err, ResultExt};
 use std::fs;
 use std::io::{Read, Write};
 use xdg::BaseDirectories;
 
-pub fn create_default_config_file() {
+pub fn create_default_config_file() -> crate::Result<()> {
     info!("Creating default config file");
-    let xdg_dirs: BaseDirectories = BaseDirectories::with_prefix("volan").unwrap();
+    let xdg_dirs: BaseDirectories = BaseDirectories::with_prefix("volan")?;
     let config_path: std::path::PathBuf = xdg_dirs
         .place_config_file("config.yaml")
-        .expect("Could not create config file");
-    let mut config_file = fs::File::create(config_path).expect("Failed to write config file");
+        .context("Could not create config file")?;
+    let mut config_file = fs::File::create(config_path).context("Failed to write config file")?;
     config_file
         .write_all(DEFAULT_CONFIG.as_bytes())
-        .expect("Could not write config");
+        .context("Could not write config")?;
+    Ok(())
 }
 
-pub fn null_check_config() {
+pub fn null_check_config() -> crate::Result<()> {
     if !config_file_exists() {
-        create_default_config_file();
+        create_default_config_file()?;
     }
+    Ok(())
 }
 
-pub fn read_config_file() -> String {
-    info!("Reading config file");
-    let xdg_dirs: BaseDirectories = BaseDirectories::with_prefix("volan").unwrap();
-    let config_file_path: std::path::PathBuf = xdg_dirs.find_config_file("config.yaml").unwrap();
-    let mut file: fs::File = fs::File::open(config_file_path).expect("Could not open config file");
+pub fn read_config_file() -> crate::Result<String> {
+    let xdg_dirs: BaseDirectories = BaseDirectories::with_prefix("volan")?;
+    let config_file_path: std::path::PathBuf = xdg_dirs
+        .find_config_file("config.yaml")
+        .ok_or_else(|| format_err!("Could not find config file"))?;
+    read_config_file_at(&config_file_path)
+}
+
+pub fn read_config_file_at(path: &std::path::Path) -> crate::Result<String> {
+    info!("Reading config file {:?}", path);
+    let mut file: fs::File =
+        fs::File::open(path).with_context(|_| format!("Could not open config file {:?}", path))?;
     let mut contents: String = String::new();
-    file.read_to_string(&mut contents).unwrap();
-    contents
+    file.read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+pub fn config_file_path() -> Option<std::path::PathBuf> {
+    let xdg_dirs: BaseDirectories = BaseDirectories::with_prefix("volan").ok()?;
+    xdg_dirs.find_config_file("config.yaml")
 }
 
 pub fn config_file_exists() -> bool {
     info!("Checking if the config file exists");
-    let xdg_dirs: BaseDirectories = BaseDirectories::with_prefix("volan").unwrap();
-    xdg_dirs.find_config_file("config.yaml").is_some()
+    config_file_path().is_some()
 }
 
-static DEFAULT_CONFIG: &str = "
+pub static DEFAULT_CONFIG: &str = "
 # Masks and command arguments can be separated by pipe symbols (|)
 # Example:
 # {command: mkdir, args: -p|dir1|dir2|dir3, key:XK_n, masks: Mod1|Shift}
+#
+# Keys are X keysym names such as XK_Return, XK_F1 or XF86AudioRaiseVolume.
+# The XK_ prefix is optional. Keys typed with Shift can be bound by what
+# they type, so Super+exclam is the same as Super+Shift+1 on a US keyboard.
+#
+# Instead of masks and key, a binding can be given as a single chord, e.g.
+# {function: FocusNext, bind: Super+Shift+j}. Ctrl, Alt and Super can be used
+# for Control, Mod1 and Mod4, and $mod for the mod set below.
+#
+# Several chords separated by spaces are pressed one after the other, e.g.
+# {function: CloseFocused, bind: $mod+x c}.
+#
+# Adding `on: release` to a key or spawn binding runs it when the key is
+# released rather than pressed, e.g.
+# {command: scrot, args: -s, key: XK_Print, masks: Mod1, on: release}
+
+mod: Mod1
 
 key_bindings:
   - {function: CloseFocused,      masks: Mod1,          key: XK_w  }
@@ -52,6 +83,14 @@ key_bindings:
   - {function: DecreaseInnerGaps, masks: Mod1,          key: XK_2  }
   - {function: IncreaseOuterGaps, masks: Mod1|Shift,    key: XK_1  }
   - {function: DecreaseOuterGaps, masks: Mod1|Shift,    key: XK_2  }
+  - {function: ToggleFloating,    masks: Mod1|Shift,    key: XK_space}
+  - {function: ToggleFullscreen,  masks: Mod1,          key: XK_m  }
+  - {function: FocusMonitorNext,  masks: Mod1,          key: XK_period}
+  - {function: FocusMonitorPrev,  masks: Mod1,          key: XK_comma}
+  - {function: MoveWindowToMonitor,           masks: Mod1|Shift, key: XK_period}
+  - {function: SwapWorkspacesBetweenMonitors, masks: Mod1|Shift, key: XK_comma}
+  - {function: EnterMode, mode: resize, bind: $mod+r}
+  - {function: ReloadConfig,      masks: Mod1|Shift,    key: XK_r}
 
 spawn_bindings:
   - {command: pkill,          args: Xorg,             key: XK_q,      masks: Mod1}
@@ -60,6 +99,22 @@ spawn_bindings:
   - {command: emacs,          args:,                  key: XK_space,  masks: Mod1}
   - {command: dmenu_run,      args:,                  key: XK_p,      masks: Mod1}
 
+# Buttons are numbered as in X: 1 is left, 2 is middle and 3 is right.
+mouse_bindings:
+  - {action: Move,    masks: Mod1,    button: 1}
+  - {action: Resize,  masks: Mod1,    button: 3}
+
+# While a mode is active, its bindings replace all of the others. Bindings
+# in a mode usually leave out the mod, and should include one to ExitMode.
+modes:
+  resize:
+    - {function: DecreaseMaster,  bind: h}
+    - {function: IncreaseMaster,  bind: l}
+    - {function: FocusNext,       bind: j}
+    - {function: FocusPrev,       bind: k}
+    - {function: ExitMode,        bind: Escape}
+    - {function: ExitMode,        bind: Return}
+
 work_spaces:
   - {name: alpha,     layout: tile,           key: XK_a,      masks: Mod1}
   - {name: beta,      layout: tile,           key: XK_s,      masks: Mod1}
@@ -69,4 +124,38 @@ work_spaces:
 gaps:
   inner: 0
   outer: 0
+
+# Colours must be quoted, as YAML treats # as the start of a comment.
+borders:
+  width: 2
+  focused: '#5294e2'
+  unfocused: '#2f343f'
+  urgent: '#e06c75'
+  floating: '#8fbcbb'
+
+# One of: click, sloppy, strict
+focus_policy: click
+
+# Switching to a workspace shown on another monitor either focuses that
+# monitor (focus) or brings the workspace over to this one (swap).
+workspace_switching: focus
+
+# Programs to start with the window manager. Those under exec are started
+# again whenever the config is reloaded, those under exec_once only at
+# startup. The first window of a program given a workspace is put there,
+# as long as the window belongs to the program itself: windows of programs
+# which fork, or are started through a launcher script, go to the current
+# workspace instead.
+# autostart:
+#   - {exec_once: nm-applet}
+#   - {exec: feh, args: --bg-scale|/path/to/wallpaper.png}
+#   - {exec_once: firefox, workspace: 2}
+
+# The log is rotated once it gets bigger than max_size (rotate: size), at
+# the start of each day (rotate: daily) or never. Sizes may end in K, M or G.
+logging:
+  rotate: size
+  max_size: 10M
+  keep: 5
+  compress: true
 ";
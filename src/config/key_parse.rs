@@ -1,82 +1,144 @@
+use crate::mouse::Button;
 use crate::ModKey;
+use std::ffi::{CStr, CString};
 use std::str::FromStr;
-use x11::keysym;
+use x11::xlib;
 
-pub fn parse_mask_and_key(
-    mask: String,
-    xk_key: String,
-) -> Result<(Vec<ModKey>, super::XKeyValue), ()> {
-    Ok((parse_mask_keys(mask), safe_xk_parse(&xk_key)?))
+/// Parses a chord of mods and a key joined by `+`, such as
+/// `Super+Shift+Return`.
+pub fn parse_chord(chord: &str) -> Result<super::Chord, String> {
+    let mut parts: Vec<&str> = chord.split('+').map(str::trim).collect();
+    let key: &str = parts.pop().unwrap_or_default();
+    let mods: Vec<ModKey> = parts
+        .into_iter()
+        .map(parse_mod)
+        .collect::<Result<Vec<ModKey>, String>>()?;
+    Ok((mods, safe_xk_parse(key)?))
 }
 
-pub fn parse_mask_keys(mask: String) -> Vec<ModKey> {
-    let mut result: Vec<ModKey> = Vec::new();
-    for key in split_mask_keys(mask) {
-        result.push(str_to_mask_key(key));
+/// Parses chords separated by whitespace, such as `Super+w h`, which are
+/// pressed one after the other.
+pub fn parse_sequence(sequence: &str) -> Result<Vec<super::Chord>, String> {
+    let chords: Vec<super::Chord> = sequence
+        .split_whitespace()
+        .map(parse_chord)
+        .collect::<Result<Vec<super::Chord>, String>>()?;
+    if chords.is_empty() {
+        return Err("empty key binding".to_string());
     }
-    result
+    Ok(chords)
 }
 
-fn split_mask_keys(pipe_separated_masks: String) -> Vec<String> {
-    pipe_separated_masks
-        .split("|")
-        .map(|i| i.to_string())
+/// Parses mods separated by pipe symbols, such as `Mod1|Shift`.
+pub fn parse_mask_keys(mask: &str) -> Result<Vec<ModKey>, String> {
+    mask.split('|')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(parse_mod)
         .collect()
 }
 
-fn str_to_mask_key(str_mask: String) -> ModKey {
-    if let Ok(parsed_key) = ModKey::from_str(&str_mask) {
-        parsed_key
-    } else {
-        error!(
-            "Mask key {}, is not a valid key defaulting to Mod1",
-            str_mask
-        );
-        ModKey::Mod1
+fn parse_mod(string: &str) -> Result<ModKey, String> {
+    ModKey::from_str(string).map_err(|_| format!("unknown modifier `{}`", string))
+}
+
+/// Parses a pointer button, given either as a number or as e.g. `Button1`.
+pub fn parse_button(string: &str) -> Result<Button, String> {
+    match string.trim_start_matches("Button").parse::<Button>() {
+        Ok(button) if button >= 1 => Ok(button),
+        _ => Err(format!("unknown button `{}`", string)),
     }
 }
 
-pub fn safe_xk_parse(string: &str) -> Result<u32, ()> {
-    match string {
-        "XK_a" => Ok(keysym::XK_a),
-        "XK_b" => Ok(keysym::XK_b),
-        "XK_c" => Ok(keysym::XK_c),
-        "XK_d" => Ok(keysym::XK_d),
-        "XK_e" => Ok(keysym::XK_e),
-        "XK_f" => Ok(keysym::XK_f),
-        "XK_g" => Ok(keysym::XK_g),
-        "XK_h" => Ok(keysym::XK_h),
-        "XK_i" => Ok(keysym::XK_i),
-        "XK_j" => Ok(keysym::XK_j),
-        "XK_k" => Ok(keysym::XK_k),
-        "XK_l" => Ok(keysym::XK_l),
-        "XK_m" => Ok(keysym::XK_m),
-        "XK_n" => Ok(keysym::XK_n),
-        "XK_o" => Ok(keysym::XK_o),
-        "XK_p" => Ok(keysym::XK_p),
-        "XK_q" => Ok(keysym::XK_q),
-        "XK_r" => Ok(keysym::XK_r),
-        "XK_s" => Ok(keysym::XK_s),
-        "XK_t" => Ok(keysym::XK_t),
-        "XK_u" => Ok(keysym::XK_u),
-        "XK_v" => Ok(keysym::XK_v),
-        "XK_w" => Ok(keysym::XK_w),
-        "XK_x" => Ok(keysym::XK_x),
-        "XK_y" => Ok(keysym::XK_y),
-        "XK_z" => Ok(keysym::XK_z),
-        "XK_0" => Ok(keysym::XK_0),
-        "XK_1" => Ok(keysym::XK_1),
-        "XK_2" => Ok(keysym::XK_2),
-        "XK_3" => Ok(keysym::XK_3),
-        "XK_4" => Ok(keysym::XK_4),
-        "XK_5" => Ok(keysym::XK_5),
-        "XK_6" => Ok(keysym::XK_6),
-        "XK_7" => Ok(keysym::XK_7),
-        "XK_8" => Ok(keysym::XK_8),
-        "XK_9" => Ok(keysym::XK_9),
-        "XK_Return" => Ok(keysym::XK_Return),
-        "XK_Tab" => Ok(keysym::XK_Tab),
-        "XK_space" => Ok(keysym::XK_space),
-        _ => Err(()),
+/// Returns the name of a keysym, such as `Return`, or its number if it
+/// doesn't have one.
+pub fn keysym_name(keysym: u32) -> String {
+    let name = unsafe { xlib::XKeysymToString(xlib::KeySym::from(keysym)) };
+    if name.is_null() {
+        return format!("{:#x}", keysym);
+    }
+    unsafe { CStr::from_ptr(name) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Resolves a keysym name, such as `Return`, `F1`, `Print` or
+/// `XF86AudioRaiseVolume`, with or without an `XK_` prefix.
+pub fn safe_xk_parse(string: &str) -> Result<u32, String> {
+    let name: &str = string.trim();
+    let name: &str = name.strip_prefix("XK_").unwrap_or(name);
+    let unknown = || format!("unknown key `{}`", string.trim());
+    let name: CString = CString::new(name).map_err(|_| unknown())?;
+    // This only looks the name up in Xlib's tables, so doesn't need a
+    // connection to the X server.
+    match unsafe { xlib::XStringToKeysym(name.as_ptr()) } {
+        0 => Err(unknown()),
+        keysym => Ok(keysym as u32),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_button, parse_chord, parse_mask_keys, parse_sequence};
+    use crate::ModKey;
+    use x11::keysym::{XF86XK_AudioRaiseVolume, XK_Return, XK_exclam, XK_h, XK_w, XK_A};
+
+    #[test]
+    fn test_parse_chord() {
+        assert_eq!(
+            parse_chord("Super+Shift+Return"),
+            Ok((vec![ModKey::Mod4, ModKey::Shift], XK_Return))
+        );
+        assert_eq!(
+            parse_chord("Ctrl + Alt + XK_Return"),
+            Ok((vec![ModKey::Control, ModKey::Mod1], XK_Return))
+        );
+        assert_eq!(
+            parse_chord("XF86AudioRaiseVolume"),
+            Ok((vec![], XF86XK_AudioRaiseVolume))
+        );
+        assert_eq!(
+            parse_chord("Hyper+Return"),
+            Err("unknown modifier `Hyper`".to_string())
+        );
+        assert_eq!(
+            parse_chord("Mod1+Retrun"),
+            Err("unknown key `Retrun`".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_shifted_keys() {
+        // Keys typed with Shift keep their own keysyms, which are grabbed
+        // along with Shift and matched against what the key types with it.
+        assert_eq!(parse_chord("Super+A"), Ok((vec![ModKey::Mod4], XK_A)));
+        assert_eq!(parse_chord("exclam"), Ok((vec![], XK_exclam)));
+    }
+
+    #[test]
+    fn test_parse_sequence() {
+        assert_eq!(
+            parse_sequence("Super+w  h"),
+            Ok(vec![(vec![ModKey::Mod4], XK_w), (vec![], XK_h)])
+        );
+        assert!(parse_sequence("  ").is_err());
+    }
+
+    #[test]
+    fn test_parse_mask_keys() {
+        assert_eq!(
+            parse_mask_keys("Mod1|Shift"),
+            Ok(vec![ModKey::Mod1, ModKey::Shift])
+        );
+        assert_eq!(parse_mask_keys(""), Ok(vec![]));
+        assert!(parse_mask_keys("Mod1|Meta").is_err());
+    }
+
+    #[test]
+    fn test_parse_button() {
+        assert_eq!(parse_button("1"), Ok(1));
+        assert_eq!(parse_button("Button3"), Ok(3));
+        assert!(parse_button("0").is_err());
+        assert!(parse_button("Left").is_err());
     }
 }
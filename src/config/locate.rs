@@ -0,0 +1,172 @@
+//! Finds things in the text of the config file, to say where errors are.
+//!
+//! serde_yaml only knows positions while it is parsing, so once the config
+//! has been deserialized we look for the lines again. This understands just
+//! enough of YAML's block structure for the config file: nested mappings,
+//! and lists of entries written one per line or over several lines.
+
+use std::ops::Range;
+
+/// A line and column, both counted from 1.
+pub type Position = (usize, usize);
+
+pub struct Locator<'a> {
+    lines: Vec<&'a str>,
+}
+
+impl<'a> Locator<'a> {
+    pub fn new(text: &'a str) -> Locator<'a> {
+        Locator {
+            lines: text.lines().collect(),
+        }
+    }
+
+    /// Returns where the mapping key at the end of a path, such as
+    /// `["borders", "focused"]`, is written.
+    pub fn key(&self, path: &[&str]) -> Option<Position> {
+        self.block(path).map(|(position, _)| position)
+    }
+
+    /// Returns where an entry of the list under a key starts.
+    pub fn entry(&self, path: &[&str], index: usize) -> Option<Position> {
+        let line: usize = self.entry_lines(path, index)?.start;
+        Some((line + 1, indent(self.lines[line]) + 1))
+    }
+
+    /// Returns where a field of an entry of the list under a key is written,
+    /// or where the entry starts if the field isn't there.
+    pub fn entry_field(&self, path: &[&str], index: usize, field: &str) -> Option<Position> {
+        let lines: Range<usize> = self.entry_lines(path, index)?;
+        lines
+            .clone()
+            .find_map(|line| find_key(self.lines[line], field).map(|column| (line + 1, column + 1)))
+            .or_else(|| self.entry(path, index))
+    }
+
+    /// Returns the position of a key along with the range of lines nested
+    /// under it.
+    fn block(&self, path: &[&str]) -> Option<(Position, Range<usize>)> {
+        let mut range: Range<usize> = 0..self.lines.len();
+        let mut position: Position = (0, 0);
+        for name in path {
+            let line: usize = range.clone().find(|&line| {
+                let text: &str = self.lines[line].trim_start();
+                text.strip_prefix(name)
+                    .is_some_and(|rest| rest.trim_start().starts_with(':'))
+            })?;
+            let key_indent: usize = indent(self.lines[line]);
+            let end: usize = (line + 1..range.end)
+                .find(|&next| {
+                    let text: &str = self.lines[next].trim_start();
+                    let next_indent: usize = indent(self.lines[next]);
+                    !(is_blank(text)
+                        || next_indent > key_indent
+                        || (next_indent == key_indent && text.starts_with('-')))
+                })
+                .unwrap_or(range.end);
+            position = (line + 1, key_indent + 1);
+            range = line + 1..end;
+        }
+        Some((position, range))
+    }
+
+    /// Returns the lines of an entry of the list under a key.
+    fn entry_lines(&self, path: &[&str], index: usize) -> Option<Range<usize>> {
+        let (_, block) = self.block(path)?;
+        let items: Vec<usize> = block
+            .clone()
+            .filter(|&line| self.lines[line].trim_start().starts_with('-'))
+            .collect();
+        let item_indent: usize = items.iter().map(|&line| indent(self.lines[line])).min()?;
+        let starts: Vec<usize> = items
+            .into_iter()
+            .filter(|&line| indent(self.lines[line]) == item_indent)
+            .collect();
+        let start: usize = *starts.get(index)?;
+        let end: usize = starts.get(index + 1).cloned().unwrap_or(block.end);
+        Some(start..end)
+    }
+}
+
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+fn is_blank(text: &str) -> bool {
+    text.is_empty() || text.starts_with('#')
+}
+
+/// Returns the byte offset of a mapping key in a line, if it's there.
+fn find_key(line: &str, key: &str) -> Option<usize> {
+    line.match_indices(key)
+        .find(|&(offset, _)| {
+            let before: Option<char> = line[..offset].chars().last();
+            let after: &str = line[offset + key.len()..].trim_start();
+            before.is_none_or(|c| " \t{,-".contains(c)) && after.starts_with(':')
+        })
+        .map(|(offset, _)| offset)
+}
+
+#[cfg(test)]
+mod test {
+    use super::Locator;
+
+    const CONFIG: &str = "\
+# A comment.
+key_bindings:
+  - {function: FocusNext, bind: Mod1+j}
+  - function: FocusPrev
+
+    bind: Mod1+k
+modes:
+  resize:
+    - {function: ExitMode, key: Escape}
+    # Another comment.
+    - bind: h
+      function: DecreaseMaster
+borders:
+  focused: '#5294e2'
+";
+
+    #[test]
+    fn test_key() {
+        let locator = Locator::new(CONFIG);
+        assert_eq!(locator.key(&["key_bindings"]), Some((2, 1)));
+        assert_eq!(locator.key(&["modes", "resize"]), Some((8, 3)));
+        assert_eq!(locator.key(&["borders", "focused"]), Some((14, 3)));
+        assert_eq!(locator.key(&["borders", "unfocused"]), None);
+        assert_eq!(locator.key(&["gaps"]), None);
+    }
+
+    #[test]
+    fn test_entry() {
+        let locator = Locator::new(CONFIG);
+        assert_eq!(locator.entry(&["key_bindings"], 0), Some((3, 3)));
+        assert_eq!(locator.entry(&["key_bindings"], 1), Some((4, 3)));
+        assert_eq!(locator.entry(&["key_bindings"], 2), None);
+        assert_eq!(locator.entry(&["modes", "resize"], 1), Some((11, 5)));
+    }
+
+    #[test]
+    fn test_entry_field() {
+        let locator = Locator::new(CONFIG);
+        assert_eq!(
+            locator.entry_field(&["key_bindings"], 0, "bind"),
+            Some((3, 27))
+        );
+        // A field of a multi-line entry, after a blank line.
+        assert_eq!(
+            locator.entry_field(&["key_bindings"], 1, "bind"),
+            Some((6, 5))
+        );
+        assert_eq!(
+            locator.entry_field(&["modes", "resize"], 1, "function"),
+            Some((12, 7))
+        );
+        // A field which isn't there points at the entry.
+        assert_eq!(
+            locator.entry_field(&["key_bindings"], 1, "masks"),
+            Some((4, 3))
+        );
+    }
+}
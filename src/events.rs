@@ -0,0 +1,69 @@
+//! Notifications about changes in window manager state.
+//!
+//! Events are queued as they happen and broadcast to subscribed control
+//! socket clients once the WM has finished handling the current X event or
+//! command, as newline-delimited JSON.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use serde::Serialize;
+
+use crate::x::WindowId;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WmEvent {
+    WorkspaceSwitched {
+        from: String,
+        to: String,
+    },
+    WindowManaged {
+        window: WindowId,
+        workspace: Option<String>,
+    },
+    WindowUnmanaged {
+        window: WindowId,
+    },
+    FocusChanged {
+        workspace: String,
+        window: Option<WindowId>,
+    },
+    LayoutChanged {
+        workspace: String,
+        layout: Option<String>,
+    },
+    GapsChanged {
+        workspace: String,
+        inner: u32,
+        outer: u32,
+    },
+    /// A keymap mode was entered, or left if `mode` is `None`.
+    ModeChanged {
+        mode: Option<String>,
+    },
+    ConfigReloaded,
+}
+
+/// A shared queue of events waiting to be broadcast.
+#[derive(Clone, Default)]
+pub struct EventQueue {
+    queue: Rc<RefCell<VecDeque<WmEvent>>>,
+}
+
+impl EventQueue {
+    pub fn new() -> EventQueue {
+        EventQueue::default()
+    }
+
+    pub fn push(&self, event: WmEvent) {
+        debug!("Queueing event: {:?}", event);
+        self.queue.borrow_mut().push_back(event);
+    }
+
+    /// Removes and returns every queued event, oldest first.
+    pub fn drain(&self) -> Vec<WmEvent> {
+        self.queue.borrow_mut().drain(..).collect()
+    }
+}
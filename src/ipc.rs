@@ -0,0 +1,527 @@
+//! A local control socket for driving the window manager from scripts.
+//!
+//! Clients connect to a Unix domain socket, `volan:<display>.sock` under
+//! `$XDG_RUNTIME_DIR/volan` or else `$VOLAN_SOCKET`, and send
+//! newline-terminated commands such as `FocusNext`, `switch_group beta`,
+//! `enter_mode resize` or `spawn alacritty -e htop`. A command may also be
+//! sent as a JSON array of words, such as `["spawn", "notify-send", "a b"]`,
+//! for words with spaces in them. Each command receives a single line of
+//! JSON in reply.
+//!
+//! The `get_tree` query replies with a snapshot of the whole window manager
+//! state (see `tree::Tree`) in the reply's `data` field.
+//!
+//! After sending `subscribe`, a client receives every `events::WmEvent` as a
+//! line of JSON until it disconnects.
+
+use std::cell::{Cell, RefCell};
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use failure::{format_err, ResultExt};
+use serde::{Deserialize, Serialize};
+
+use crate::cmd::{self, Command};
+use crate::config::lazy_commands;
+use crate::events::WmEvent;
+use crate::Result;
+
+/// Returns the path of the control socket, creating its directory if needed.
+///
+/// Each display has its own socket, so that instances running side by side
+/// (e.g. under Xephyr) don't take over each other's.
+pub fn socket_path() -> Result<PathBuf> {
+    if let Some(path) = std::env::var_os("VOLAN_SOCKET") {
+        return Ok(PathBuf::from(path));
+    }
+    let display: String = std::env::var("DISPLAY").unwrap_or_default();
+    let xdg_dirs: xdg::BaseDirectories = xdg::BaseDirectories::with_prefix("volan")?;
+    let path: PathBuf = xdg_dirs
+        .place_runtime_file(socket_name(&display))
+        .context("Could not create runtime directory for the control socket")?;
+    Ok(path)
+}
+
+/// Returns the name of the socket for a display, leaving out the screen
+/// number so that e.g. `:1` and `:1.0` share a socket.
+fn socket_name(display: &str) -> String {
+    let display: &str = match display.rfind(':') {
+        Some(colon) => match display[colon..].find('.') {
+            Some(dot) => &display[..colon + dot],
+            None => display,
+        },
+        None => display,
+    };
+    format!("volan{}.sock", display.replace('/', "_"))
+}
+
+/// Returns whether something is listening on a socket, as opposed to it
+/// having been left behind.
+fn socket_in_use(path: &Path) -> bool {
+    UnixStream::connect(path).is_ok()
+}
+
+/// Identifies a client connected to the control socket.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ClientId(u64);
+
+/// A single command line received from a client.
+#[derive(Debug)]
+pub struct Request {
+    pub client: ClientId,
+    pub message: String,
+}
+
+/// What a client asked for.
+pub enum Message {
+    /// Run a command against the WM.
+    Command(Command),
+    /// Reply with the state tree.
+    GetTree,
+    /// Start streaming events to the client.
+    Subscribe,
+}
+
+impl Request {
+    /// Parses the request into a `Message` that can be acted upon by the WM.
+    pub fn parse(&self) -> Result<Message> {
+        let mut words = self.words()?.into_iter();
+        let name: String = words.next().ok_or_else(|| format_err!("Empty command"))?;
+        let name: &str = name.as_str();
+        let args: Vec<String> = words.collect();
+        let command: Command = match name {
+            "get_tree" => return Ok(Message::GetTree),
+            "subscribe" => return Ok(Message::Subscribe),
+            "switch_group" => cmd::lazy::switch_group(Self::single_arg(name, args)?),
+            "enter_mode" => cmd::lazy::enter_mode(Self::single_arg(name, args)?),
+            "move_window_to_group" => {
+                cmd::lazy::move_window_to_group(Self::single_arg(name, args)?)
+            }
+            "spawn" => {
+                let mut args = args.into_iter();
+                let program: String = args
+                    .next()
+                    .ok_or_else(|| format_err!("spawn requires a program to run"))?;
+                cmd::lazy::spawn(program, args.collect())
+            }
+            _ => {
+                let action = lazy_commands::ActionTypes::from_str(name)
+                    .map_err(|_| format_err!("Unknown command: {}", name))?;
+                lazy_commands::get_cmd_based_on_action(&action)
+            }
+        };
+        Ok(Message::Command(command))
+    }
+
+    /// Splits the request into words, either on whitespace or as a JSON
+    /// array.
+    fn words(&self) -> Result<Vec<String>> {
+        if self.message.trim_start().starts_with('[') {
+            Ok(serde_json::from_str(&self.message)
+                .context("Commands starting with [ must be a JSON array of strings")?)
+        } else {
+            Ok(self.message.split_whitespace().map(String::from).collect())
+        }
+    }
+
+    fn single_arg(name: &str, args: Vec<String>) -> Result<String> {
+        match args.as_slice() {
+            [arg] => Ok(arg.clone()),
+            _ => Err(format_err!("{} takes exactly one argument", name)),
+        }
+    }
+}
+
+/// The reply sent back to a client for each request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Reply {
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub data: Option<serde_json::Value>,
+}
+
+impl Reply {
+    pub fn ok() -> Reply {
+        Reply {
+            success: true,
+            error: None,
+            data: None,
+        }
+    }
+
+    pub fn data(data: serde_json::Value) -> Reply {
+        Reply {
+            success: true,
+            error: None,
+            data: Some(data),
+        }
+    }
+
+    pub fn error<S: Into<String>>(error: S) -> Reply {
+        Reply {
+            success: false,
+            error: Some(error.into()),
+            data: None,
+        }
+    }
+}
+
+/// The longest request a client may send, so that a client which never sends
+/// a newline can't make us buffer without end.
+const MAX_REQUEST: usize = 64 << 10;
+
+/// How much output may wait for a client to read it before the client is
+/// dropped, as a subscriber which never reads would otherwise make it grow
+/// forever.
+const MAX_OUTGOING: usize = 4 << 20;
+
+struct Client {
+    id: ClientId,
+    stream: UnixStream,
+    buffer: Vec<u8>,
+    /// Replies and events not yet written, as the socket was full.
+    outgoing: Vec<u8>,
+    subscribed: bool,
+}
+
+impl Client {
+    /// Queues a line to be written and writes as much as the socket will
+    /// take. Returns whether the client can still be written to.
+    fn send(&mut self, line: &str) -> bool {
+        if self.outgoing.len() + line.len() + 1 > MAX_OUTGOING {
+            error!(
+                "Control client {:?} isn't reading its replies, dropping it",
+                self.id
+            );
+            return false;
+        }
+        self.outgoing.extend_from_slice(line.as_bytes());
+        self.outgoing.push(b'\n');
+        self.flush()
+    }
+
+    /// Writes as much of the queued output as the socket will take. Returns
+    /// whether the client can still be written to.
+    fn flush(&mut self) -> bool {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return false,
+                Ok(n) => {
+                    self.outgoing.drain(..n);
+                }
+                Err(ref error) if error.kind() == ErrorKind::WouldBlock => return true,
+                Err(ref error) if error.kind() == ErrorKind::Interrupted => continue,
+                Err(error) => {
+                    error!("Could not write to control client {:?}: {}", self.id, error);
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+/// The listening side of the control socket.
+///
+/// Both the listener and the client streams are non-blocking, so the X event
+/// loop can poll their file descriptors alongside the X connection.
+pub struct Server {
+    path: PathBuf,
+    listener: UnixListener,
+    clients: RefCell<Vec<Client>>,
+    next_id: Cell<u64>,
+}
+
+impl Server {
+    /// Binds the control socket, replacing any stale socket left behind by a
+    /// previous instance. Fails if another instance is still listening on it.
+    pub fn bind() -> Result<Server> {
+        let path: PathBuf = socket_path()?;
+        if socket_in_use(&path) {
+            return Err(format_err!(
+                "Control socket {:?} is in use by another instance, set VOLAN_SOCKET to use another socket",
+                path
+            ));
+        }
+        if path.exists() {
+            std::fs::remove_file(&path)
+                .with_context(|_| format!("Could not remove stale socket {:?}", path))?;
+        }
+        let listener: UnixListener = UnixListener::bind(&path)
+            .with_context(|_| format!("Could not bind control socket {:?}", path))?;
+        listener.set_nonblocking(true)?;
+        info!("Listening for commands on {:?}", path);
+        Ok(Server {
+            path,
+            listener,
+            clients: RefCell::new(Vec::new()),
+            next_id: Cell::new(0),
+        })
+    }
+
+    /// Returns the file descriptors that should be polled, with the events to
+    /// poll each for: readability, and writability for clients with output
+    /// waiting.
+    pub fn fds(&self) -> Vec<(RawFd, libc::c_short)> {
+        let mut fds: Vec<(RawFd, libc::c_short)> = vec![(self.listener.as_raw_fd(), libc::POLLIN)];
+        fds.extend(self.clients.borrow().iter().map(|c| {
+            let events: libc::c_short = if c.outgoing.is_empty() {
+                libc::POLLIN
+            } else {
+                libc::POLLIN | libc::POLLOUT
+            };
+            (c.stream.as_raw_fd(), events)
+        }));
+        fds
+    }
+
+    /// Accepts pending connections, writes any output waiting for clients and
+    /// reads from all of them, returning every complete request received.
+    ///
+    /// Clients which have disconnected or errored are dropped.
+    pub fn read_requests(&self) -> Vec<Request> {
+        self.accept();
+
+        let mut requests: Vec<Request> = Vec::new();
+        self.clients.borrow_mut().retain_mut(|client| {
+            let open: bool = client.flush() && Self::read_client(client, &mut requests);
+            if !open {
+                debug!("Control client {:?} disconnected", client.id);
+            }
+            open
+        });
+        requests
+    }
+
+    fn accept(&self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(error) = stream.set_nonblocking(true) {
+                        error!("Could not make control client non-blocking: {}", error);
+                        continue;
+                    }
+                    let id = ClientId(self.next_id.get());
+                    self.next_id.set(id.0 + 1);
+                    debug!("Accepted control client {:?}", id);
+                    self.clients.borrow_mut().push(Client {
+                        id,
+                        stream,
+                        buffer: Vec::new(),
+                        outgoing: Vec::new(),
+                        subscribed: false,
+                    });
+                }
+                Err(ref error) if error.kind() == ErrorKind::WouldBlock => return,
+                Err(error) => {
+                    error!("Could not accept control client: {}", error);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Reads everything available from a client, splitting it into requests.
+    /// Returns whether the client is still connected.
+    ///
+    /// A client sending a request longer than `MAX_REQUEST` is sent an error
+    /// and disconnected.
+    fn read_client(client: &mut Client, requests: &mut Vec<Request>) -> bool {
+        let mut chunk = [0u8; 1024];
+        loop {
+            match client.stream.read(&mut chunk) {
+                Ok(0) => return false,
+                Ok(n) => client.buffer.extend_from_slice(&chunk[..n]),
+                Err(ref error) if error.kind() == ErrorKind::WouldBlock => return true,
+                Err(ref error) if error.kind() == ErrorKind::Interrupted => continue,
+                Err(error) => {
+                    error!(
+                        "Could not read from control client {:?}: {}",
+                        client.id, error
+                    );
+                    return false;
+                }
+            }
+
+            while let Some(newline) = client.buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = client.buffer.drain(..=newline).collect();
+                let message: String = String::from_utf8_lossy(&line).trim().to_string();
+                if !message.is_empty() {
+                    requests.push(Request {
+                        client: client.id,
+                        message,
+                    });
+                }
+            }
+            if client.buffer.len() > MAX_REQUEST {
+                error!(
+                    "Control client {:?} sent a request over {} bytes, dropping it",
+                    client.id, MAX_REQUEST
+                );
+                let reply = Reply::error(format!("Requests can't be over {} bytes", MAX_REQUEST));
+                if let Ok(json) = serde_json::to_string(&reply) {
+                    client.send(&json);
+                }
+                return false;
+            }
+        }
+    }
+
+    /// Sends a reply to a client, if it is still connected.
+    pub fn reply(&self, client: ClientId, reply: &Reply) {
+        match serde_json::to_string(reply) {
+            Ok(json) => self.send(client, &json),
+            Err(error) => error!("Could not serialize reply {:?}: {}", reply, error),
+        }
+    }
+
+    /// Marks a client as wanting to receive events.
+    pub fn subscribe(&self, client: ClientId) {
+        for c in self.clients.borrow_mut().iter_mut() {
+            if c.id == client {
+                c.subscribed = true;
+            }
+        }
+    }
+
+    /// Sends an event to every subscribed client.
+    pub fn broadcast(&self, event: &WmEvent) {
+        let json: String = match serde_json::to_string(event) {
+            Ok(json) => json,
+            Err(error) => {
+                error!("Could not serialize event {:?}: {}", event, error);
+                return;
+            }
+        };
+        self.send_where(|c| c.subscribed, &json);
+    }
+
+    fn send(&self, client: ClientId, line: &str) {
+        self.send_where(|c| c.id == client, line);
+    }
+
+    /// Sends a line to every client matching the predicate, dropping any
+    /// which can no longer be written to. Whatever doesn't fit in a client's
+    /// socket is written once it has room.
+    fn send_where<P>(&self, predicate: P, line: &str)
+    where
+        P: Fn(&Client) -> bool,
+    {
+        self.clients
+            .borrow_mut()
+            .retain_mut(|c| !predicate(c) || c.send(line));
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        if let Err(error) = std::fs::remove_file(&self.path) {
+            error!("Could not remove control socket {:?}: {}", self.path, error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    use super::{socket_name, Client, ClientId, Request, Server, MAX_OUTGOING, MAX_REQUEST};
+
+    /// Returns a client, and the other end of its socket.
+    fn client() -> (Client, UnixStream) {
+        let (stream, other) = UnixStream::pair().unwrap();
+        stream.set_nonblocking(true).unwrap();
+        let client = Client {
+            id: ClientId(0),
+            stream,
+            buffer: Vec::new(),
+            outgoing: Vec::new(),
+            subscribed: true,
+        };
+        (client, other)
+    }
+
+    fn words(message: &str) -> Vec<String> {
+        Request {
+            client: ClientId(0),
+            message: message.to_string(),
+        }
+        .words()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_socket_name() {
+        assert_eq!(socket_name(":0"), "volan:0.sock");
+        assert_eq!(socket_name(":1.0"), "volan:1.sock");
+        assert_eq!(socket_name("localhost:10.2"), "volanlocalhost:10.sock");
+        assert_eq!(
+            socket_name("/tmp/launch-x/org.xquartz:0"),
+            "volan_tmp_launch-x_org.xquartz:0.sock"
+        );
+        assert_eq!(socket_name(""), "volan.sock");
+    }
+
+    #[test]
+    fn test_words() {
+        assert_eq!(words("spawn foo a b"), vec!["spawn", "foo", "a", "b"]);
+        assert_eq!(
+            words(r#"["spawn", "foo", "a b"]"#),
+            vec!["spawn", "foo", "a b"]
+        );
+    }
+
+    #[test]
+    fn test_send_when_full() {
+        let (mut client, other) = client();
+        // More than the socket's buffer holds.
+        let line: String = "x".repeat(1 << 20);
+        assert!(client.send(&line));
+        assert!(!client.outgoing.is_empty());
+        assert!(client.send("second"));
+
+        let reader = std::thread::spawn(move || {
+            BufReader::new(other)
+                .lines()
+                .map(|line| line.unwrap())
+                .collect::<Vec<String>>()
+        });
+        while !client.outgoing.is_empty() {
+            assert!(client.flush());
+        }
+        drop(client);
+        let lines: Vec<String> = reader.join().unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], line);
+        assert_eq!(lines[1], "second");
+    }
+
+    #[test]
+    fn test_send_to_client_not_reading() {
+        let (mut client, _other) = client();
+        let line: String = "x".repeat(MAX_OUTGOING / 4);
+        let sent: usize = (0..8).take_while(|_| client.send(&line)).count();
+        assert!(sent < 8);
+    }
+
+    #[test]
+    fn test_request_too_long() {
+        let (mut client, mut other) = client();
+        let mut requests: Vec<Request> = Vec::new();
+        other.write_all(b"FocusNext\n").unwrap();
+        other.write_all(&vec![b'x'; MAX_REQUEST + 1]).unwrap();
+        assert!(!Server::read_client(&mut client, &mut requests));
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].message, "FocusNext");
+
+        let mut reply = String::new();
+        BufReader::new(other).read_line(&mut reply).unwrap();
+        assert!(reply.contains(r#""success":false"#));
+    }
+}
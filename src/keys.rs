@@ -1,22 +1,30 @@
 use std::collections::HashMap;
 use std::os::raw::c_uint;
 
+use x11::keysym;
+
 use crate::cmd::Command;
 
 /// Represents a modifier key.
+///
+/// As well as their X names, `Control`, `Mod1` and `Mod4` can be parsed from
+/// the names of the keys usually mapped to them: `Ctrl`, `Alt` and `Super`.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, EnumString)]
 pub enum ModKey {
     Shift,
     Lock,
+    #[strum(serialize = "Control", serialize = "Ctrl")]
     Control,
+    #[strum(serialize = "Mod1", serialize = "Alt")]
     Mod1,
     Mod2,
     Mod3,
+    #[strum(serialize = "Mod4", serialize = "Super")]
     Mod4,
     Mod5,
 }
 
-type ModMask = c_uint;
+pub type ModMask = c_uint;
 
 impl ModKey {
     pub fn mask_all() -> ModMask {
@@ -30,6 +38,11 @@ impl ModKey {
             | xcb::MOD_MASK_5
     }
 
+    /// Returns the mask with all of the given mods set.
+    pub fn combine(mods: &[ModKey]) -> ModMask {
+        mods.iter().fold(0, |mask, mod_key| mask | mod_key.mask())
+    }
+
     fn mask(self) -> ModMask {
         match self {
             ModKey::Shift => xcb::MOD_MASK_SHIFT,
@@ -47,40 +60,475 @@ impl ModKey {
 /// A single key, of the same type as the `x11::keysym` constants.
 type Key = c_uint;
 
-/// A combination of zero or more mods and a key.
+/// A combination of zero or more mods and a key, either being pressed or
+/// released.
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct KeyCombo {
     pub mod_mask: ModMask,
     pub keysym: Key,
+    pub release: bool,
 }
 
 impl KeyCombo {
-    fn new(mods: &[ModKey], keysym: Key) -> KeyCombo {
-        let mod_mask: u32 = mods.iter().fold(0, |mask, mod_key| mask | mod_key.mask());
-        KeyCombo { mod_mask, keysym }
+    fn new(mods: &[ModKey], keysym: Key, release: bool) -> KeyCombo {
+        let mod_mask: u32 = ModKey::combine(mods);
+        KeyCombo {
+            mod_mask,
+            keysym,
+            release,
+        }
     }
 }
 
+/// What a key leads to in a keymap.
+enum KeyBinding {
+    Command(Command),
+    /// The key starts a sequence, whose next key is looked up in this keymap.
+    Prefix(KeyMap),
+}
+
+type KeyMap = HashMap<KeyCombo, KeyBinding>;
+
+/// A sequence of keys, each pressed after the last, such as `Super+w` then
+/// `h`.
+pub type KeySequence = Vec<(Vec<ModKey>, Key)>;
+
+/// What should happen after a key press.
+pub enum KeyAction {
+    /// Run the bound command.
+    Run(Command),
+    /// The key is part of a sequence, or is bound on release: wait for the
+    /// next key or for it to be released.
+    Pending,
+    /// Nothing is bound to the key. Any sequence in progress is abandoned.
+    Unbound,
+}
+
+/// Maps keys to commands.
+///
+/// As well as the usual keymap, there can be named modes whose keymaps
+/// replace it while they're active. Keymaps can hold sequences of keys, so
+/// the handlers keep track of the keys pressed so far.
 pub struct KeyHandlers {
-    hashmap: HashMap<KeyCombo, Command>,
+    root: KeyMap,
+    modes: HashMap<String, KeyMap>,
+    mode: Option<String>,
+    /// The keys of the sequence pressed so far.
+    pending: Vec<KeyCombo>,
+    /// A key bound on release which is held down. Its release runs the
+    /// binding even if some of the mods were let go of first.
+    awaiting_release: Option<KeyCombo>,
 }
 
 impl KeyHandlers {
+    /// Returns the first keys of the usual keymap, which need to be grabbed.
+    /// Grabbing a key delivers both its presses and releases.
     pub fn key_combos(&self) -> Vec<&KeyCombo> {
-        self.hashmap.keys().collect()
+        self.root.keys().collect()
+    }
+
+    /// Adds a binding for a sequence of keys.
+    pub fn add_sequence(&mut self, sequence: KeySequence, command: Command) {
+        Self::insert(&mut self.root, &sequence, false, command);
+    }
+
+    /// Adds a binding which runs when the last key of a sequence is
+    /// released, rather than pressed.
+    pub fn add_release(&mut self, sequence: KeySequence, command: Command) {
+        Self::insert(&mut self.root, &sequence, true, command);
+    }
+
+    /// Adds a named mode with its own keymap.
+    pub fn add_mode(&mut self, name: String, bindings: Vec<(KeySequence, Command)>) {
+        let keymap: &mut KeyMap = self.modes.entry(name).or_default();
+        for (sequence, command) in bindings {
+            Self::insert(keymap, &sequence, false, command);
+        }
+    }
+
+    /// Inserts a sequence into a keymap. `release` applies to the last key;
+    /// the keys before it are always pressed.
+    fn insert(
+        keymap: &mut KeyMap,
+        sequence: &[(Vec<ModKey>, Key)],
+        release: bool,
+        command: Command,
+    ) {
+        let ((mods, keysym), rest) = match sequence.split_first() {
+            Some(split) => split,
+            None => return,
+        };
+        let combo = KeyCombo::new(mods, *keysym, release && rest.is_empty());
+        if rest.is_empty() {
+            if keymap
+                .insert(combo.clone(), KeyBinding::Command(command))
+                .is_some()
+            {
+                warn!("Key {:?} was bound more than once", combo);
+            }
+            return;
+        }
+        let binding: &mut KeyBinding = keymap
+            .entry(combo.clone())
+            .or_insert_with(|| KeyBinding::Prefix(KeyMap::new()));
+        if let KeyBinding::Command(_) = binding {
+            warn!("Key {:?} is both bound and starts a sequence", combo);
+            *binding = KeyBinding::Prefix(KeyMap::new());
+        }
+        if let KeyBinding::Prefix(next) = binding {
+            Self::insert(next, rest, release, command);
+        }
     }
 
-    pub fn get(&self, key_combo: &KeyCombo) -> Option<Command> {
-        self.hashmap.get(key_combo).cloned()
+    pub fn mode(&self) -> Option<&str> {
+        self.mode.as_deref()
+    }
+
+    pub fn has_mode(&self, name: &str) -> bool {
+        self.modes.contains_key(name)
+    }
+
+    /// Switches to a named mode, or back to the usual keymap. Any sequence
+    /// in progress is abandoned.
+    pub fn set_mode(&mut self, mode: Option<String>) {
+        self.mode = mode;
+        self.pending.clear();
+        self.awaiting_release = None;
+    }
+
+    /// Returns whether every key press should come to us, rather than just
+    /// those grabbed, because a sequence or mode is in progress.
+    pub fn wants_keyboard(&self) -> bool {
+        self.mode.is_some() || !self.pending.is_empty()
+    }
+
+    /// Returns the keymap which the next key is looked up in, following on
+    /// from any sequence in progress.
+    fn current_keymap(&self) -> Option<&KeyMap> {
+        let mut keymap: Option<&KeyMap> = match &self.mode {
+            Some(mode) => self.modes.get(mode),
+            None => Some(&self.root),
+        };
+        for combo in self.pending.iter() {
+            keymap = match keymap.and_then(|keymap| keymap.get(combo)) {
+                Some(KeyBinding::Prefix(next)) => Some(next),
+                _ => None,
+            };
+        }
+        keymap
+    }
+
+    /// Looks up a key which can be bound more than one way, such as Shift+1
+    /// which also types `exclam`, going with the first way that is bound
+    /// either on press or on release.
+    pub fn press_any(&mut self, key_combos: &[KeyCombo]) -> KeyAction {
+        let keymap: Option<&KeyMap> = self.current_keymap();
+        let bound: Option<&KeyCombo> = key_combos.iter().find(|combo| {
+            keymap.is_some_and(|keymap| {
+                keymap.contains_key(combo)
+                    || keymap.contains_key(&KeyCombo {
+                        release: true,
+                        ..(*combo).clone()
+                    })
+            })
+        });
+        match bound.or_else(|| key_combos.first()) {
+            Some(key_combo) => self.press(key_combo),
+            None => KeyAction::Unbound,
+        }
+    }
+
+    /// Looks up a key press or release, following on from any sequence in
+    /// progress.
+    pub fn press(&mut self, key_combo: &KeyCombo) -> KeyAction {
+        // Mods are pressed on the way to the next key of a sequence.
+        if !self.pending.is_empty() && is_modifier(key_combo.keysym) {
+            return KeyAction::Pending;
+        }
+
+        // The release of a key bound on release runs the binding whatever
+        // mods are still held. Pressing any other key gives up on it.
+        let key_combo: &KeyCombo = &match self.awaiting_release.take() {
+            Some(awaited) if key_combo.release && awaited.keysym == key_combo.keysym => KeyCombo {
+                release: true,
+                ..awaited
+            },
+            Some(awaited) if key_combo.release => {
+                self.awaiting_release = Some(awaited);
+                key_combo.clone()
+            }
+            _ => key_combo.clone(),
+        };
+
+        let keymap: Option<&KeyMap> = self.current_keymap();
+        let action: KeyAction = match keymap.and_then(|keymap| keymap.get(key_combo)) {
+            Some(KeyBinding::Command(command)) => KeyAction::Run(command.clone()),
+            Some(KeyBinding::Prefix(_)) => KeyAction::Pending,
+            // Keys are released on the way to the next key of a sequence.
+            None if key_combo.release => return KeyAction::Unbound,
+            // The key is bound on release, so wait for that.
+            None if keymap.is_some_and(|keymap| {
+                keymap.contains_key(&KeyCombo {
+                    release: true,
+                    ..key_combo.clone()
+                })
+            }) =>
+            {
+                self.awaiting_release = Some(key_combo.clone());
+                return KeyAction::Pending;
+            }
+            None => KeyAction::Unbound,
+        };
+
+        match action {
+            KeyAction::Pending => self.pending.push(key_combo.clone()),
+            _ => self.pending.clear(),
+        }
+        action
     }
 }
 
+/// Returns whether the keysym is for a modifier key, like Shift_L or
+/// ISO_Level3_Shift.
+fn is_modifier(keysym: Key) -> bool {
+    (keysym::XK_Shift_L..=keysym::XK_Hyper_R).contains(&keysym)
+        || (keysym::XK_ISO_Lock..=keysym::XK_ISO_Level5_Lock).contains(&keysym)
+}
+
 impl From<Vec<(Vec<ModKey>, Key, Command)>> for KeyHandlers {
     fn from(handlers: Vec<(Vec<ModKey>, Key, Command)>) -> KeyHandlers {
-        let mut hashmap = HashMap::new();
+        let mut key_handlers = KeyHandlers {
+            root: KeyMap::new(),
+            modes: HashMap::new(),
+            mode: None,
+            pending: Vec::new(),
+            awaiting_release: None,
+        };
         for (modkeys, keysym, handler) in handlers {
-            hashmap.insert(KeyCombo::new(&modkeys, keysym), handler);
+            key_handlers.add_sequence(vec![(modkeys, keysym)], handler);
+        }
+        key_handlers
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use x11::keysym::{
+        XK_Escape, XK_Shift_L, XK_Super_L, XK_a, XK_exclam, XK_h, XK_p, XK_w, XK_1, XK_A,
+    };
+
+    use super::{KeyAction, KeyCombo, KeyHandlers, ModKey};
+    use crate::cmd::Command;
+
+    fn command() -> Command {
+        Rc::new(|_| Ok(()))
+    }
+
+    fn press(mods: &[ModKey], keysym: u32) -> KeyCombo {
+        KeyCombo::new(mods, keysym, false)
+    }
+
+    fn release(mods: &[ModKey], keysym: u32) -> KeyCombo {
+        KeyCombo::new(mods, keysym, true)
+    }
+
+    fn runs(action: KeyAction, expected: &Command) -> bool {
+        match action {
+            KeyAction::Run(command) => Rc::ptr_eq(&command, expected),
+            _ => false,
         }
-        KeyHandlers { hashmap }
+    }
+
+    fn handlers() -> KeyHandlers {
+        KeyHandlers::from(Vec::new())
+    }
+
+    #[test]
+    fn test_sequence() {
+        let resize: Command = command();
+        let mut keys: KeyHandlers = handlers();
+        keys.add_sequence(
+            vec![(vec![ModKey::Mod4], XK_w), (vec![], XK_h)],
+            resize.clone(),
+        );
+
+        assert!(matches!(
+            keys.press(&press(&[ModKey::Mod4], XK_w)),
+            KeyAction::Pending
+        ));
+        assert!(keys.wants_keyboard());
+        assert!(runs(keys.press(&press(&[], XK_h)), &resize));
+        assert!(!keys.wants_keyboard());
+    }
+
+    #[test]
+    fn test_sequence_ignores_mods_and_releases() {
+        let resize: Command = command();
+        let mut keys: KeyHandlers = handlers();
+        keys.add_sequence(
+            vec![(vec![ModKey::Mod4], XK_w), (vec![ModKey::Shift], XK_h)],
+            resize.clone(),
+        );
+
+        keys.press(&press(&[ModKey::Mod4], XK_w));
+        assert!(matches!(
+            keys.press(&release(&[ModKey::Mod4], XK_w)),
+            KeyAction::Unbound
+        ));
+        assert!(matches!(
+            keys.press(&press(&[], XK_Shift_L)),
+            KeyAction::Pending
+        ));
+        assert!(runs(keys.press(&press(&[ModKey::Shift], XK_h)), &resize));
+    }
+
+    #[test]
+    fn test_unbound_key_abandons_sequence() {
+        let resize: Command = command();
+        let mut keys: KeyHandlers = handlers();
+        keys.add_sequence(vec![(vec![ModKey::Mod4], XK_w), (vec![], XK_h)], resize);
+
+        keys.press(&press(&[ModKey::Mod4], XK_w));
+        assert!(matches!(keys.press(&press(&[], XK_p)), KeyAction::Unbound));
+        assert!(!keys.wants_keyboard());
+        assert!(matches!(keys.press(&press(&[], XK_h)), KeyAction::Unbound));
+    }
+
+    #[test]
+    fn test_release() {
+        let talk: Command = command();
+        let mut keys: KeyHandlers = handlers();
+        keys.add_release(vec![(vec![ModKey::Mod4], XK_p)], talk.clone());
+
+        assert!(matches!(
+            keys.press(&press(&[ModKey::Mod4], XK_p)),
+            KeyAction::Pending
+        ));
+        assert!(runs(keys.press(&release(&[ModKey::Mod4], XK_p)), &talk));
+    }
+
+    #[test]
+    fn test_release_after_mods() {
+        let talk: Command = command();
+        let mut keys: KeyHandlers = handlers();
+        keys.add_release(vec![(vec![ModKey::Mod4], XK_p)], talk.clone());
+
+        keys.press(&press(&[ModKey::Mod4], XK_p));
+        assert!(matches!(
+            keys.press(&release(&[ModKey::Mod4], XK_Super_L)),
+            KeyAction::Unbound
+        ));
+        assert!(runs(keys.press(&release(&[], XK_p)), &talk));
+        assert!(!keys.wants_keyboard());
+
+        // Pressing another key in between gives up on it.
+        keys.press(&press(&[ModKey::Mod4], XK_p));
+        keys.press(&press(&[ModKey::Mod4], XK_h));
+        assert!(matches!(
+            keys.press(&release(&[], XK_p)),
+            KeyAction::Unbound
+        ));
+    }
+
+    #[test]
+    fn test_release_after_mods_ending_sequence() {
+        let talk: Command = command();
+        let close: Command = command();
+        let mut keys: KeyHandlers = handlers();
+        keys.add_release(
+            vec![(vec![ModKey::Mod4], XK_w), (vec![ModKey::Mod4], XK_h)],
+            talk.clone(),
+        );
+        keys.add_sequence(vec![(vec![ModKey::Mod4], XK_p)], close.clone());
+
+        keys.press(&press(&[ModKey::Mod4], XK_w));
+        keys.press(&press(&[ModKey::Mod4], XK_h));
+        keys.press(&release(&[ModKey::Mod4], XK_Super_L));
+        assert!(runs(keys.press(&release(&[], XK_h)), &talk));
+        assert!(!keys.wants_keyboard());
+        // The next key isn't taken as part of the sequence.
+        assert!(runs(keys.press(&press(&[ModKey::Mod4], XK_p)), &close));
+    }
+
+    #[test]
+    fn test_release_ending_sequence() {
+        let talk: Command = command();
+        let mut keys: KeyHandlers = handlers();
+        keys.add_release(
+            vec![(vec![ModKey::Mod4], XK_w), (vec![], XK_h)],
+            talk.clone(),
+        );
+
+        keys.press(&press(&[ModKey::Mod4], XK_w));
+        keys.press(&release(&[ModKey::Mod4], XK_w));
+        assert!(matches!(keys.press(&press(&[], XK_h)), KeyAction::Pending));
+        assert!(keys.wants_keyboard());
+        assert!(runs(keys.press(&release(&[], XK_h)), &talk));
+        assert!(!keys.wants_keyboard());
+    }
+
+    #[test]
+    fn test_mode() {
+        let close: Command = command();
+        let shrink: Command = command();
+        let exit: Command = command();
+        let mut keys: KeyHandlers = handlers();
+        keys.add_sequence(vec![(vec![ModKey::Mod4], XK_w)], close.clone());
+        keys.add_mode(
+            "resize".to_string(),
+            vec![
+                (vec![(vec![], XK_h)], shrink.clone()),
+                (vec![(vec![], XK_Escape)], exit.clone()),
+            ],
+        );
+        assert!(keys.has_mode("resize"));
+        assert!(!keys.has_mode("move"));
+
+        keys.set_mode(Some("resize".to_string()));
+        assert_eq!(keys.mode(), Some("resize"));
+        assert!(keys.wants_keyboard());
+        assert!(runs(keys.press(&press(&[], XK_h)), &shrink));
+        // The usual keymap is replaced while the mode is active.
+        assert!(matches!(
+            keys.press(&press(&[ModKey::Mod4], XK_w)),
+            KeyAction::Unbound
+        ));
+        assert!(runs(keys.press(&press(&[], XK_Escape)), &exit));
+
+        keys.set_mode(None);
+        assert!(!keys.wants_keyboard());
+        assert!(runs(keys.press(&press(&[ModKey::Mod4], XK_w)), &close));
+        assert!(matches!(keys.press(&press(&[], XK_h)), KeyAction::Unbound));
+    }
+
+    #[test]
+    fn test_shifted_keysyms() {
+        let shout: Command = command();
+        let bang: Command = command();
+        let mut keys: KeyHandlers = handlers();
+        keys.add_sequence(vec![(vec![ModKey::Mod4], XK_A)], shout.clone());
+        keys.add_sequence(vec![(vec![], XK_exclam)], bang.clone());
+
+        // Shift+1 on a US keyboard, which is both `Shift+1` and `exclam`.
+        let shift_1: Vec<KeyCombo> = vec![press(&[ModKey::Shift], XK_1), press(&[], XK_exclam)];
+        assert!(runs(keys.press_any(&shift_1), &bang));
+        assert!(runs(
+            keys.press_any(&[
+                press(&[ModKey::Mod4, ModKey::Shift], XK_a),
+                press(&[ModKey::Mod4], XK_A),
+            ]),
+            &shout
+        ));
+
+        // A binding for the unshifted key comes first.
+        let one: Command = command();
+        keys.add_sequence(vec![(vec![ModKey::Shift], XK_1)], one.clone());
+        assert!(runs(keys.press_any(&shift_1), &one));
+        assert!(matches!(
+            keys.press_any(&[press(&[], XK_1)]),
+            KeyAction::Unbound
+        ));
     }
 }
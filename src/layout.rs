@@ -38,6 +38,12 @@ pub trait Layout: LayoutClone {
     fn decrease_innergaps(&mut self);
     fn decrease_outergaps(&mut self);
     fn increase_outergaps(&mut self);
+    /// Returns the current (inner, outer) gaps.
+    fn gaps(&self) -> (u32, u32);
+    fn set_gaps(&mut self, innergaps: u32, outergaps: u32);
+    /// Sets the width of the border drawn around each window, which is kept
+    /// within the space the layout gives the window.
+    fn set_border_width(&mut self, border_width: u32);
 }
 
 impl Clone for Box<dyn Layout> {
@@ -52,9 +58,17 @@ impl fmt::Debug for dyn Layout {
     }
 }
 
-fn configure_single_window(connection: &Connection, viewport: &Viewport, window_id: &WindowId) {
+fn configure_single_window(
+    connection: &Connection,
+    viewport: &Viewport,
+    window_id: &WindowId,
+    border_width: u32,
+) {
     connection.disable_window_tracking(window_id);
     connection.map_window(window_id);
-    connection.configure_window(window_id, &WindowGeometry::default(viewport));
+    connection.configure_window(
+        window_id,
+        &WindowGeometry::default(viewport).inside_border(border_width),
+    );
     connection.enable_window_tracking(window_id);
 }
@@ -11,6 +11,7 @@ pub struct CenterMaster {
     tile_resized_width: i16,
     outergaps: u32,
     innergaps: u32,
+    border_width: u32,
 }
 
 impl Layout for CenterMaster {
@@ -36,6 +37,7 @@ impl Layout for CenterMaster {
             let mut tile_layout: super::tile::TileLayout =
                 super::tile::TileLayout::new("tmp_tl_cmaster", self.innergaps, self.outergaps);
             tile_layout.resized_width = self.tile_resized_width;
+            tile_layout.border_width = self.border_width;
             tile_layout.layout(connection, viewport, stack, &Some(*master_id));
         } else {
             self.c_master(connection, viewport, stack, master_id);
@@ -87,6 +89,19 @@ impl Layout for CenterMaster {
         self.outergaps += 1;
         info!("Increased outergaps to {}", self.outergaps);
     }
+
+    fn gaps(&self) -> (u32, u32) {
+        (self.innergaps, self.outergaps)
+    }
+
+    fn set_gaps(&mut self, innergaps: u32, outergaps: u32) {
+        self.innergaps = innergaps;
+        self.outergaps = outergaps;
+    }
+
+    fn set_border_width(&mut self, border_width: u32) {
+        self.border_width = border_width;
+    }
 }
 
 impl CenterMaster {
@@ -97,6 +112,7 @@ impl CenterMaster {
             innergaps,
             outergaps,
             tile_resized_width: 160,
+            border_width: 0,
         }
     }
 
@@ -126,7 +142,7 @@ impl CenterMaster {
         window_id: &WindowId,
     ) {
         let normal_geometry = self.get_normal_geometry(i, stack, viewport);
-        connection.configure_window(window_id, &normal_geometry);
+        connection.configure_window(window_id, &normal_geometry.inside_border(self.border_width));
     }
 
     fn configure_master_window(
@@ -136,7 +152,7 @@ impl CenterMaster {
         window_id: &WindowId,
     ) {
         let master_geometry = self.get_master_geometry(viewport);
-        connection.configure_window(window_id, &master_geometry);
+        connection.configure_window(window_id, &master_geometry.inside_border(self.border_width));
     }
 
     fn get_normal_geometry(
@@ -158,10 +174,10 @@ impl CenterMaster {
         let width = ((self.resized_width * -2) + (viewport.width / 2 + viewport.width / 16) as i16)
             as u32
             - self.innergaps * 2;
-        let x = (viewport.width - width) / 2 + self.outergaps;
+        let x = viewport.x + (viewport.width - width) / 2 + self.outergaps;
         WindowGeometry {
             x,
-            y: self.outergaps,
+            y: viewport.y + self.outergaps,
             width,
             height: viewport.height - self.outergaps * 2,
         }
@@ -192,8 +208,8 @@ mod left_stack {
         ) -> WindowGeometry {
             let left_stack_len: u32 = stack_length / 2;
             WindowGeometry {
-                x: self.outergaps,
-                y: self.calculate_left_stack_y(viewport, stack_length, i),
+                x: viewport.x + self.outergaps,
+                y: viewport.y + self.calculate_left_stack_y(viewport, stack_length, i),
                 width: (self.resized_width + ((viewport.width - master_width) / 2) as i16) as u32,
                 height: self.calculate_left_stack_heigth(viewport, left_stack_len),
             }
@@ -249,8 +265,9 @@ mod right_stack {
         ) -> WindowGeometry {
             let right_stack_len: u32 = (stack_length - 1) / 2;
             WindowGeometry {
-                x: self.calculate_right_stack_x(master_width, viewport),
-                y: self.calculate_right_stack_y(stack_length, right_stack_len, viewport, i),
+                x: viewport.x + self.calculate_right_stack_x(master_width, viewport),
+                y: viewport.y
+                    + self.calculate_right_stack_y(stack_length, right_stack_len, viewport, i),
                 width: self.calculate_right_stack_width(viewport, master_width),
                 height: self.calculate_right_stack_height(viewport, right_stack_len),
             }
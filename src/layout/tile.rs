@@ -9,6 +9,7 @@ pub struct TileLayout {
     pub resized_width: i16,
     outergaps: u32,
     innergaps: u32,
+    pub border_width: u32,
 }
 
 impl Layout for TileLayout {
@@ -31,7 +32,7 @@ impl Layout for TileLayout {
             master.as_ref().unwrap()
         };
         if stack.len() < 2 {
-            super::configure_single_window(connection, viewport, master_id);
+            super::configure_single_window(connection, viewport, master_id, self.border_width);
         } else {
             self.tile(connection, viewport, stack, master_id);
         }
@@ -76,6 +77,19 @@ impl Layout for TileLayout {
         self.outergaps += 1;
         info!("Increased outergaps to {}", self.outergaps);
     }
+
+    fn gaps(&self) -> (u32, u32) {
+        (self.innergaps, self.outergaps)
+    }
+
+    fn set_gaps(&mut self, innergaps: u32, outergaps: u32) {
+        self.innergaps = innergaps;
+        self.outergaps = outergaps;
+    }
+
+    fn set_border_width(&mut self, border_width: u32) {
+        self.border_width = border_width;
+    }
 }
 
 impl TileLayout {
@@ -85,6 +99,7 @@ impl TileLayout {
             resized_width: 160,
             innergaps,
             outergaps,
+            border_width: 0,
         }
     }
 
@@ -114,7 +129,7 @@ impl TileLayout {
         window_id: &WindowId,
     ) {
         let normal_geometry: WindowGeometry = self.get_normal_geometry(i, stack, viewport);
-        connection.configure_window(window_id, &normal_geometry);
+        connection.configure_window(window_id, &normal_geometry.inside_border(self.border_width));
     }
 
     fn configure_master_window(
@@ -124,7 +139,10 @@ impl TileLayout {
         window_id: &WindowId,
     ) {
         let focused_geometry: WindowGeometry = self.get_master_geometry(viewport);
-        connection.configure_window(window_id, &focused_geometry);
+        connection.configure_window(
+            window_id,
+            &focused_geometry.inside_border(self.border_width),
+        );
     }
 
     fn get_normal_geometry(
@@ -133,7 +151,8 @@ impl TileLayout {
         stack: &Stack<WindowId>,
         viewport: &Viewport,
     ) -> WindowGeometry {
-        let x: u32 = ((viewport.width / 2) as i16 + self.resized_width) as u32 + self.innergaps;
+        let x: u32 =
+            viewport.x + ((viewport.width / 2) as i16 + self.resized_width) as u32 + self.innergaps;
         let width: u32 = ((viewport.width / 2) as i16 - self.resized_width) as u32
             - self.outergaps
             - self.innergaps;
@@ -142,7 +161,7 @@ impl TileLayout {
             - self.innergaps;
         WindowGeometry {
             x,
-            y: self.outergaps + (i as u32 * (height + self.innergaps)),
+            y: viewport.y + self.outergaps + (i as u32 * (height + self.innergaps)),
             width,
             height,
         }
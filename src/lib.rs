@@ -6,27 +6,47 @@ extern crate strum_macros;
 
 pub mod cmd;
 pub mod config;
+pub mod events;
+pub mod ipc;
 mod keys;
 pub mod layout;
+mod logging;
+mod monitor;
+mod mouse;
 pub mod screen;
+pub mod settings;
+mod signals;
 mod stack;
+pub mod tree;
 mod workspaces;
 mod x;
 
-pub use crate::{keys::ModKey, screen::Screen, stack::Stack, workspaces::WorkSpaceBuilder};
+pub use crate::{
+    keys::{KeyHandlers, KeySequence, ModKey},
+    logging::set_log_rotation,
+    mouse::MouseAction,
+    screen::Screen,
+    settings::{FocusPolicy, LogRotation, Rotate, Settings, WorkspaceSwitching},
+    stack::Stack,
+    workspaces::WorkSpaceBuilder,
+};
 use {
-    crate::x::{Connection, StrutPartial, WindowId},
-    failure::{Error, ResultExt},
+    crate::x::{Connection, StrutPartial, WindowGeometry, WindowId},
+    failure::{format_err, Error, ResultExt},
+    serde::Serialize,
 };
 
 use {
     crate::{
-        keys::{KeyCombo, KeyHandlers},
+        events::{EventQueue, WmEvent},
+        keys::{KeyAction, KeyCombo},
         layout::Layout,
-        workspaces::WorkSpace,
-        x::{Event, WindowType},
+        monitor::Monitor,
+        mouse::{MouseCombo, MouseHandlers},
+        workspaces::{RemovedWindow, WorkSpace},
+        x::{Event, Output, PointerPosition, StateAction, WindowState, WindowType},
     },
-    std::rc::Rc,
+    std::{cmp, rc::Rc},
 };
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -35,16 +55,43 @@ pub mod keysym {
     pub use x11::keysym::*;
 }
 
-/// Initializes a logger using the default configuration.
-pub fn intiailize_logger() -> Result<()> {
+/// Where log messages go and how many of them.
+#[derive(Clone, Debug)]
+pub struct LogOptions {
+    pub level: log::LevelFilter,
+    /// The file to append to. If unset, `volan.log` in the XDG data directory
+    /// is used. How it's rotated comes from the config, through
+    /// `set_log_rotation()`.
+    pub file: Option<std::path::PathBuf>,
+    /// Whether to log to stdout as well as to the file.
+    pub stdout: bool,
+}
+
+impl Default for LogOptions {
+    fn default() -> Self {
+        LogOptions {
+            level: log::LevelFilter::Trace,
+            file: None,
+            stdout: true,
+        }
+    }
+}
+
+/// Initializes a logger with the given options.
+pub fn intiailize_logger(options: &LogOptions) -> Result<()> {
     log_panics::init();
 
-    let xdg_dirs: xdg::BaseDirectories = xdg::BaseDirectories::with_prefix("volan")?;
-    let log_path: std::path::PathBuf = xdg_dirs
-        .place_data_file("volan.log")
-        .context("Could not create log file")?;
+    let log_path: std::path::PathBuf = match options.file {
+        Some(ref path) => path.clone(),
+        None => {
+            let xdg_dirs: xdg::BaseDirectories = xdg::BaseDirectories::with_prefix("volan")?;
+            xdg_dirs
+                .place_data_file("volan.log")
+                .context("Could not create log file")?
+        }
+    };
 
-    fern::Dispatch::new()
+    let mut dispatch: fern::Dispatch = fern::Dispatch::new()
         .format(|out, message, record| {
             out.finish(format_args!(
                 "[{}] [{}] {}",
@@ -53,14 +100,42 @@ pub fn intiailize_logger() -> Result<()> {
                 message
             ))
         })
-        .level(log::LevelFilter::Trace)
-        .chain(std::io::stdout())
-        .chain(fern::log_file(&log_path)?)
+        .level(options.level);
+    if options.stdout {
+        dispatch = dispatch.chain(std::io::stdout());
+    }
+    dispatch
+        .chain(Box::new(
+            logging::open(&log_path)
+                .with_context(|_| format!("Could not open log file {:?}", log_path))?,
+        ) as Box<dyn std::io::Write + Send>)
         .apply()?;
 
     Ok(())
 }
 
+/// Works out the names of the existing groups when the workspaces are
+/// redefined. Groups which are still defined keep their names, and the rest
+/// take the names which are new, in order. Any groups left over keep their
+/// old names, which can't clash as they're no longer defined.
+///
+/// Returns the names for the existing groups, in their order, along with the
+/// names which need new groups.
+fn rename_groups(old_names: &[String], new_names: &[String]) -> (Vec<String>, Vec<String>) {
+    let mut unused = new_names.iter().filter(|name| !old_names.contains(name));
+    let renames: Vec<String> = old_names
+        .iter()
+        .map(|old| {
+            if new_names.contains(old) {
+                old.clone()
+            } else {
+                unused.next().unwrap_or(old).clone()
+            }
+        })
+        .collect();
+    (renames, unused.cloned().collect())
+}
+
 pub fn gen_workspaces(
     keys: Vec<config::BoundCommand>,
     groupdef: Vec<config::BoundWorkSpace>,
@@ -76,6 +151,24 @@ pub fn gen_workspaces(
     additional_keys.extend(keys);
     (additional_keys, workspaces)
 }
+
+/// Builds all of the key bindings in the config, including those generated
+/// for each workspace, along with the workspaces themselves.
+pub fn load_bindings(parser: &config::Parser) -> (KeyHandlers, Vec<WorkSpaceBuilder>) {
+    let (bound_commands, workspaces): (Vec<config::BoundCommand>, Vec<WorkSpaceBuilder>) =
+        gen_workspaces(parser.get_bound_commands(), parser.get_bound_workspaces());
+    let mut keys: KeyHandlers = bound_commands.into();
+    for (chords, command) in parser.get_bound_sequences() {
+        keys.add_sequence(chords, command);
+    }
+    for (chords, command) in parser.get_bound_release_sequences() {
+        keys.add_release(chords, command);
+    }
+    for (name, bindings) in parser.get_bound_modes() {
+        keys.add_mode(name, bindings);
+    }
+    (keys, workspaces)
+}
 #[macro_export]
 macro_rules! gen_move_window_to_group_keys {
     {
@@ -108,7 +201,7 @@ macro_rules! layouts {
     )
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
 pub struct Viewport {
     pub x: u32,
     pub y: u32,
@@ -121,57 +214,259 @@ struct Dock {
     strut_partial: Option<StrutPartial>,
 }
 
+/// A program started by autostart for a workspace, waiting for its first
+/// window.
+struct Autostarted {
+    pid: u32,
+    workspace: String,
+    /// Whether it was started by `exec_once`, rather than `exec`.
+    once: bool,
+}
+
+/// A window being dragged with a mouse binding.
+struct Drag {
+    window_id: WindowId,
+    action: MouseAction,
+    last_position: PointerPosition,
+}
+
 pub struct Volan {
     connection: Rc<Connection>,
+    ipc: Option<Rc<ipc::Server>>,
+    signals: Option<Rc<signals::Signals>>,
+    events: EventQueue,
+    settings: Settings,
     keys: KeyHandlers,
+    /// Whether we have grabbed the whole keyboard for a sequence or mode.
+    keyboard_grabbed: bool,
+    mouse: MouseHandlers,
+    drag: Option<Drag>,
     workspaces: Stack<WorkSpace>,
-    screen: Screen,
+    /// The layouts each new group starts with.
+    layouts: Vec<Box<dyn Layout>>,
+    /// One per output, each showing a different group.
+    monitors: Vec<Monitor>,
+    /// Programs started by autostart for a workspace, until their first
+    /// window is managed or they exit.
+    autostarted: Vec<Autostarted>,
 }
 
 impl Volan {
-    pub fn new<K>(
+    pub fn new<K, M>(
         keys: K,
+        mouse: M,
         workspaces: Vec<WorkSpaceBuilder>,
         layouts: &[Box<dyn Layout>],
+        settings: Settings,
     ) -> Result<Self>
     where
         K: Into<KeyHandlers>,
+        M: Into<MouseHandlers>,
     {
         let keys: KeyHandlers = keys.into();
+        let mouse: MouseHandlers = mouse.into();
+
         let connection: Rc<Connection> = Rc::new(Connection::connect()?);
         connection.install_as_wm(&keys)?;
 
+        // The WM is still usable without the control socket, so don't fail
+        // if it can't be created.
+        let ipc: Option<Rc<ipc::Server>> = match ipc::Server::bind() {
+            Ok(server) => Some(Rc::new(server)),
+            Err(error) => {
+                error!("Could not start control socket: {}", error);
+                None
+            }
+        };
+
+        // Likewise, the config can still be reloaded through a key binding.
+        let signals: Option<Rc<signals::Signals>> = match signals::Signals::install() {
+            Ok(signals) => Some(Rc::new(signals)),
+            Err(error) => {
+                error!("Could not listen for signals: {}", error);
+                None
+            }
+        };
+
+        let events: EventQueue = EventQueue::new();
         let workspaces: Stack<WorkSpace> = Stack::from(
             workspaces
                 .into_iter()
-                .map(|group: WorkSpaceBuilder| group.build(connection.clone(), layouts.to_owned()))
+                .map(|group: WorkSpaceBuilder| {
+                    group.build(
+                        connection.clone(),
+                        events.clone(),
+                        layouts.to_owned(),
+                        settings.borders,
+                    )
+                })
                 .collect::<Vec<WorkSpace>>(),
         );
 
         let mut wm: Volan = Volan {
             keys,
+            keyboard_grabbed: false,
+            mouse,
+            drag: None,
             workspaces,
+            layouts: layouts.to_owned(),
             connection: connection.clone(),
-            screen: Screen::default(),
+            ipc,
+            signals,
+            events,
+            settings,
+            monitors: Vec::new(),
+            autostarted: Vec::new(),
         };
 
+        // The first group starts with the focus, on the first monitor.
+        wm.group_mut().set_has_focus(true);
+        wm.configure_monitors(connection.get_outputs());
+
         // Learn about existing top-level windows.
         let existing_windows: Vec<WindowId> = connection.top_level_windows()?;
         for window in existing_windows {
             wm.manage_window(window);
         }
-        let viewport: Viewport = wm.viewport();
-        wm.group_mut().activate(viewport);
-        wm.connection.update_ewmh_desktops(&wm.workspaces);
+        wm.update_ewmh();
 
         Ok(wm)
     }
 
+    /// Returns the usable area of the focused monitor.
     fn viewport(&self) -> Viewport {
+        self.monitor_areas(self.focused_monitor()).0
+    }
+
+    /// Returns the usable area of a monitor along with the whole monitor.
+    fn monitor_areas(&self, index: usize) -> (Viewport, Viewport) {
         let (width, height): (u32, u32) = self
             .connection
             .get_window_geometry(self.connection.root_window_id());
-        self.screen.viewport(width, height)
+        let monitor: &Monitor = &self.monitors[index];
+        (monitor.viewport(width, height), monitor.geometry())
+    }
+
+    /// Returns the index of the monitor showing the focused group.
+    fn focused_monitor(&self) -> usize {
+        self.monitor_showing(self.group().name()).unwrap_or(0)
+    }
+
+    /// Returns the index of the monitor showing the named group, if any.
+    fn monitor_showing(&self, name: &str) -> Option<usize> {
+        self.monitors
+            .iter()
+            .position(|monitor| monitor.workspace() == Some(name))
+    }
+
+    /// Matches the monitors up with a new set of outputs and lays out every
+    /// visible group again.
+    ///
+    /// Outputs keep showing the group they showed before. Groups on outputs
+    /// which have gone away are hidden, and new outputs show the first groups
+    /// which aren't already visible.
+    fn configure_monitors(&mut self, outputs: Vec<Output>) {
+        info!("Configuring {} monitor(s)", outputs.len());
+        let docks: Vec<WindowId> = self
+            .monitors
+            .iter()
+            .flat_map(|monitor| monitor.screen().docks())
+            .map(|(window_id, _)| window_id)
+            .collect();
+        let mut shown: Vec<Option<String>> = self
+            .monitors
+            .iter()
+            .map(|monitor| monitor.workspace().map(String::from))
+            .collect();
+
+        if shown.len() > outputs.len() {
+            for name in shown.split_off(outputs.len()).into_iter().flatten() {
+                if let Some(group) = self.workspaces.iter_mut().find(|g| g.name() == name) {
+                    group.deactivate();
+                }
+            }
+        }
+        while shown.len() < outputs.len() {
+            let hidden: Option<String> = self
+                .workspaces
+                .iter()
+                .map(|group| group.name().to_string())
+                .find(|name| !shown.contains(&Some(name.clone())));
+            shown.push(hidden);
+        }
+
+        self.monitors = outputs
+            .into_iter()
+            .zip(shown)
+            .map(|(output, workspace)| Monitor::new(output, workspace))
+            .collect();
+        for dock in docks {
+            self.place_dock(dock);
+        }
+
+        // If the focused group's output has gone, focus the first monitor.
+        if self.monitor_showing(self.group().name()).is_none() {
+            if let Some(name) = self.monitors[0].workspace().map(String::from) {
+                self.focus_group(&name);
+            }
+        }
+        for index in 0..self.monitors.len() {
+            self.layout_monitor(index);
+        }
+        self.update_ewmh();
+    }
+
+    /// Lays out the group shown on a monitor, activating it if it was hidden.
+    fn layout_monitor(&mut self, index: usize) {
+        let (viewport, screen_area) = self.monitor_areas(index);
+        let name: String = match self.monitors[index].workspace() {
+            Some(name) => name.to_string(),
+            None => return,
+        };
+        if let Some(group) = self.workspaces.iter_mut().find(|g| g.name() == name) {
+            if group.is_active() {
+                group.update_viewport(viewport, screen_area);
+            } else {
+                group.activate(viewport, screen_area);
+            }
+        }
+    }
+
+    /// Adds a dock to the monitor it was placed on, returning the monitor's
+    /// index.
+    fn place_dock(&mut self, window_id: WindowId) -> usize {
+        let centre: PointerPosition = self
+            .connection
+            .get_window_rect(&window_id)
+            .map(|g| {
+                (
+                    (g.x as i32 + g.width as i32 / 2) as i16,
+                    (g.y as i32 + g.height as i32 / 2) as i16,
+                )
+            })
+            .unwrap_or((0, 0));
+        let index: usize = self
+            .monitors
+            .iter()
+            .position(|monitor| monitor.contains(centre))
+            .unwrap_or(0);
+        debug!(
+            "Placing dock {} on monitor {}",
+            window_id,
+            self.monitors[index].name()
+        );
+        self.monitors[index]
+            .screen_mut()
+            .add_dock(&self.connection, window_id);
+        index
+    }
+
+    /// Moves the focus to another group without changing what the monitors
+    /// show.
+    fn focus_group(&mut self, name: &str) {
+        self.group_mut().set_has_focus(false);
+        self.workspaces.focus(|group| group.name() == name);
+        self.group_mut().set_has_focus(true);
     }
     pub fn group(&self) -> &WorkSpace {
         self.workspaces
@@ -191,11 +486,126 @@ impl Volan {
             return;
         }
 
-        self.group_mut().deactivate();
-        self.workspaces.focus(|group| group.name() == name);
-        let viewport: Viewport = self.viewport();
-        self.group_mut().activate(viewport);
-        self.connection.update_ewmh_desktops(&self.workspaces);
+        if !self.workspaces.iter().any(|group| group.name() == name) {
+            error!("Switched to non-existent group: {}", name);
+            return;
+        }
+
+        let from: String = self.group().name().to_string();
+        if let Some(other) = self.monitor_showing(&name) {
+            // The group is already visible on another monitor, so either bring
+            // it over or just move the focus over to it.
+            if self.settings.workspace_switching == WorkspaceSwitching::Swap {
+                let monitor: usize = self.focused_monitor();
+                self.swap_monitor_workspaces(monitor, other);
+            }
+            self.focus_group(&name);
+        } else {
+            let monitor: usize = self.focused_monitor();
+            self.group_mut().deactivate();
+            self.monitors[monitor].set_workspace(Some(name.clone()));
+            self.focus_group(&name);
+            self.layout_monitor(monitor);
+        }
+        self.update_ewmh();
+        self.events.push(WmEvent::WorkspaceSwitched {
+            from,
+            to: self.group().name().to_string(),
+        });
+    }
+
+    /// Moves the focus to the next (or, with a negative offset, previous)
+    /// monitor which is showing a group, and the pointer along with it.
+    pub fn focus_monitor(&mut self, offset: isize) {
+        let target: usize = match self.monitor_at_offset(offset) {
+            Some(target) => target,
+            None => return,
+        };
+        if let Some(name) = self.monitors[target].workspace().map(String::from) {
+            info!("Focusing monitor {}", self.monitors[target].name());
+            self.switch_group(name);
+            self.warp_pointer_to_monitor(target);
+        }
+    }
+
+    /// Moves the focused window to the group on the next (or, with a negative
+    /// offset, previous) monitor. The focus stays on the current monitor.
+    pub fn move_focused_to_monitor(&mut self, offset: isize) {
+        let target: usize = match self.monitor_at_offset(offset) {
+            Some(target) => target,
+            None => return,
+        };
+        match self.monitors[target].workspace().map(String::from) {
+            Some(name) => self.move_focused_to_group(name),
+            None => error!(
+                "Cannot move window to monitor {} without a group",
+                self.monitors[target].name()
+            ),
+        }
+    }
+
+    /// Swaps the group on the focused monitor with the one on the next (or,
+    /// with a negative offset, previous) monitor. The focus stays on the
+    /// current monitor, moving to the group brought over.
+    pub fn swap_workspaces_between_monitors(&mut self, offset: isize) {
+        let target: usize = match self.monitor_at_offset(offset) {
+            Some(target) => target,
+            None => return,
+        };
+        let monitor: usize = self.focused_monitor();
+        let from: String = self.group().name().to_string();
+        self.swap_monitor_workspaces(monitor, target);
+        if let Some(name) = self.monitors[monitor].workspace().map(String::from) {
+            self.focus_group(&name);
+            self.events
+                .push(WmEvent::WorkspaceSwitched { from, to: name });
+        }
+        self.update_ewmh();
+    }
+
+    /// Returns the index of the monitor at an offset from the focused one,
+    /// wrapping around, or `None` if there is only one monitor.
+    fn monitor_at_offset(&self, offset: isize) -> Option<usize> {
+        let count: isize = self.monitors.len() as isize;
+        if count < 2 {
+            return None;
+        }
+        Some((self.focused_monitor() as isize + offset).rem_euclid(count) as usize)
+    }
+
+    /// Exchanges the groups shown on two monitors and lays both out again.
+    fn swap_monitor_workspaces(&mut self, a: usize, b: usize) {
+        let workspace_a: Option<String> = self.monitors[a].workspace().map(String::from);
+        let workspace_b: Option<String> = self.monitors[b].workspace().map(String::from);
+        info!(
+            "Swapping groups of monitors {} and {}",
+            self.monitors[a].name(),
+            self.monitors[b].name()
+        );
+        self.monitors[a].set_workspace(workspace_b);
+        self.monitors[b].set_workspace(workspace_a);
+        self.layout_monitor(a);
+        self.layout_monitor(b);
+    }
+
+    /// Moves the pointer onto the focused window of a monitor's group, or to
+    /// the middle of the monitor if the group has no windows.
+    fn warp_pointer_to_monitor(&self, index: usize) {
+        let focused: Option<WindowId> = self.monitors[index]
+            .workspace()
+            .and_then(|name| self.workspaces.iter().find(|g| g.name() == name))
+            .and_then(|group| group.focused_window().cloned());
+        match focused {
+            Some(window_id) => self.connection.warp_pointer_to(&window_id),
+            None => {
+                let area: Viewport = self.monitors[index].geometry();
+                self.connection.warp_pointer_to_position((
+                    (area.x + area.width / 2) as i16,
+                    (area.y + area.height / 2) as i16,
+                ));
+            }
+        }
+        self.connection.discard_enter_events();
     }
 
     /// Move the focused window from the active group to another named group.
@@ -215,7 +625,8 @@ impl Volan {
                 .find(|group| group.name() == name);
             match new_group {
                 Some(new_group) => {
-                    new_group.add_window(removed);
+                    new_group.add_removed_window(removed);
+                    self.update_ewmh();
                 }
                 None => {
                     // It would be nice to put the window back in its group (or avoid taking it out
@@ -227,6 +638,32 @@ impl Volan {
         }
     }
 
+    /// Publishes the current desktops and windows to EWMH clients.
+    fn update_ewmh(&self) {
+        self.connection
+            .update_ewmh(&self.workspaces, &self.workareas());
+    }
+
+    /// Returns the usable area of each group: that of the monitor showing it,
+    /// or for a hidden group that of the focused monitor, where it would be
+    /// shown if switched to.
+    fn workareas(&self) -> Vec<Viewport> {
+        let (width, height): (u32, u32) = self
+            .connection
+            .get_window_geometry(self.connection.root_window_id());
+        let focused: usize = self.focused_monitor();
+        self.workspaces
+            .iter()
+            .map(|group| {
+                let index: usize = self.monitor_showing(group.name()).unwrap_or(focused);
+                self.monitors
+                    .get(index)
+                    .map(|monitor| monitor.viewport(width, height))
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
     /// Returns whether the window is a member of any group.
     fn is_window_managed(&self, window_id: &WindowId) -> bool {
         self.workspaces.iter().any(|g| g.contains(window_id))
@@ -248,48 +685,222 @@ impl Volan {
 
         let window_types: Vec<WindowType> = self.connection.get_window_types(&window_id);
         let dock: bool = window_types.contains(&WindowType::Dock);
+        let transient_for: Option<WindowId> = self.connection.get_transient_for(&window_id);
+        let floating: bool = transient_for.is_some()
+            || window_types.iter().any(|window_type| {
+                matches!(
+                    window_type,
+                    WindowType::Dialog
+                        | WindowType::Splash
+                        | WindowType::Utility
+                        | WindowType::Menu
+                        | WindowType::Tooltip
+                        | WindowType::Notification
+                )
+            });
 
         self.connection
             .enable_window_key_events(&window_id, &self.keys);
 
         if dock {
             self.connection.map_window(&window_id);
-            self.screen.add_dock(&self.connection, window_id);
-            let viewport: Viewport = self.viewport();
-            self.group_mut().update_viewport(viewport);
+            let monitor: usize = self.place_dock(window_id);
+            self.layout_monitor(monitor);
+            self.events.push(WmEvent::WindowManaged {
+                window: window_id,
+                workspace: None,
+            });
         } else {
             self.connection.enable_window_tracking(&window_id);
-            self.group_mut().add_window(window_id);
+            // Mouse bindings are grabbed last, replacing the click to focus
+            // grab for any binding without mods.
+            if self.settings.focus_policy == FocusPolicy::Click {
+                self.connection.enable_click_to_focus(&window_id);
+            }
+            self.connection
+                .enable_window_mouse_events(&window_id, &self.mouse);
+            let geometry: Option<WindowGeometry> = if floating {
+                Some(self.centered_geometry(&window_id, transient_for))
+            } else {
+                None
+            };
+            // Windows can ask to start fullscreen by setting the state before
+            // they are mapped.
+            let fullscreen: bool = self
+                .connection
+                .get_window_states(&window_id)
+                .contains(&WindowState::Fullscreen);
+            let urgent: bool = self.connection.is_urgent(&window_id);
+            let group_name: String = self
+                .autostarted_workspace(&window_id)
+                .filter(|name| self.workspaces.iter().any(|group| group.name() == name))
+                .unwrap_or_else(|| self.group().name().to_string());
+            if let Some(group) = self
+                .workspaces
+                .iter_mut()
+                .find(|group| group.name() == group_name)
+            {
+                match geometry {
+                    Some(geometry) => group.add_floating_window(window_id, geometry),
+                    None => group.add_window(window_id),
+                }
+                if fullscreen {
+                    group.set_fullscreen(&window_id, true);
+                }
+                if urgent {
+                    group.set_urgent(&window_id, true);
+                }
+            }
+            self.events.push(WmEvent::WindowManaged {
+                window: window_id,
+                workspace: Some(group_name),
+            });
+        }
+        self.update_ewmh();
+    }
+
+    /// Starts the programs in the config's `autostart` list. Those marked
+    /// `exec_once` are only started along with the window manager, not when
+    /// the config is reloaded.
+    pub fn autostart(&mut self, programs: &[config::Autostart], starting: bool) {
+        // The `exec` programs are started again, so the last ones to be started
+        // stop waiting for a window.
+        self.autostarted.retain(|program| program.once);
+        for program in programs.iter().filter(|program| starting || !program.once) {
+            info!("Autostarting {} {:?}", program.command, program.args);
+            match std::process::Command::new(&program.command)
+                .args(&program.args)
+                .spawn()
+            {
+                Ok(child) => {
+                    if let Some(ref workspace) = program.workspace {
+                        self.autostarted.push(Autostarted {
+                            pid: child.id(),
+                            workspace: workspace.clone(),
+                            once: program.once,
+                        });
+                    }
+                }
+                Err(error) => error!("Could not autostart {}: {}", program.command, error),
+            }
+        }
+    }
+
+    /// Returns the workspace a new window should go to if it's the first
+    /// window of a program started by autostart.
+    ///
+    /// The window's _NET_WM_PID has to be that of the program itself, so
+    /// windows of programs which fork or are started through a launcher go to
+    /// the current workspace.
+    fn autostarted_workspace(&mut self, window_id: &WindowId) -> Option<String> {
+        if self.autostarted.is_empty() {
+            return None;
+        }
+        let pid: u32 = self.connection.get_window_pid(window_id)?;
+        let index: usize = self
+            .autostarted
+            .iter()
+            .position(|program| program.pid == pid)?;
+        Some(self.autostarted.remove(index).workspace)
+    }
+
+    /// Returns a geometry for a new floating window, keeping the size it asked
+    /// for but centred over its parent, or over the viewport if it doesn't
+    /// have a visible parent.
+    fn centered_geometry(&self, window_id: &WindowId, parent: Option<WindowId>) -> WindowGeometry {
+        let viewport: Viewport = self.viewport();
+        let area: WindowGeometry = parent
+            .filter(|parent| self.group().contains(parent))
+            .and_then(|parent| self.connection.get_window_rect(&parent))
+            .unwrap_or_else(|| WindowGeometry::default(&viewport));
+        let (width, height): (u32, u32) = self
+            .connection
+            .get_window_rect(window_id)
+            .map(|geometry| (geometry.width, geometry.height))
+            .unwrap_or((viewport.width / 2, viewport.height / 2));
+        let (width, height) = (
+            cmp::min(width, viewport.width),
+            cmp::min(height, viewport.height),
+        );
+        WindowGeometry {
+            x: (area.x as i32 + (area.width as i32 - width as i32) / 2) as u32,
+            y: (area.y as i32 + (area.height as i32 - height as i32) / 2) as u32,
+            width,
+            height,
         }
     }
 
-    pub fn unmanage_window(&mut self, window_id: &WindowId) {
+    /// Stops managing a window, returning whether it was managed at all.
+    pub fn unmanage_window(&mut self, window_id: &WindowId) -> bool {
         debug!("Unmanaging window: {}", window_id);
 
         // Remove the window from whichever WorkSpace it is in. Special case for
         // docks which aren't in any group.
-        self.workspaces
+        let in_group: bool = self
+            .workspaces
             .iter_mut()
             .find(|group| group.contains(window_id))
-            .map(|group| group.remove_window(window_id));
-        self.screen.remove_dock(window_id);
-
-        // The viewport may have changed.
-        let viewport: Viewport = self.viewport();
-        self.group_mut().update_viewport(viewport);
+            .map(|group| group.remove_window(window_id))
+            .is_some();
+        let dock_monitor: Option<usize> = self.monitors.iter().position(|monitor| {
+            monitor
+                .screen()
+                .docks()
+                .iter()
+                .any(|(dock, _)| dock == window_id)
+        });
+        if let Some(index) = dock_monitor {
+            // The monitor's viewport has changed.
+            self.monitors[index].screen_mut().remove_dock(window_id);
+            self.layout_monitor(index);
+        }
+        if !in_group && dock_monitor.is_none() {
+            return false;
+        }
+        self.events
+            .push(WmEvent::WindowUnmanaged { window: *window_id });
+        self.update_ewmh();
+        true
     }
 
     pub fn run(mut self) {
         info!("Started WM, entering event loop.");
         let event_loop_connection: Rc<Connection> = self.connection.clone();
-        let event_loop: x::EventLoop = event_loop_connection.get_event_loop();
+        let event_loop_ipc: Option<Rc<ipc::Server>> = self.ipc.clone();
+        let event_loop_signals: Option<Rc<signals::Signals>> = self.signals.clone();
+        let event_loop: x::EventLoop = event_loop_connection
+            .get_event_loop(event_loop_ipc.as_deref(), event_loop_signals.as_deref());
         for event in event_loop {
             match event {
                 Event::MapRequest(window_id) => self.on_map_request(window_id),
                 Event::UnmapNotify(window_id) => self.on_unmap_notify(&window_id),
                 Event::DestroyNotify(window_id) => self.on_destroy_notify(&window_id),
-                Event::KeyPress(key) => self.on_key_press(key),
+                Event::KeyPress(keys) | Event::KeyRelease(keys) => self.on_key(&keys),
+                Event::EnterNotify(window_id) => self.on_enter_notify(window_id),
+                Event::ButtonPress(window_id, combo, position) => {
+                    self.on_button_press(window_id, combo, position)
+                }
+                Event::MotionNotify(position) => self.on_motion_notify(position),
+                Event::ButtonRelease => self.on_button_release(),
+                Event::KeyboardMappingChanged => self.on_keyboard_mapping_changed(),
+                Event::WindowStateRequest(window_id, action, states) => {
+                    self.on_window_state_request(&window_id, action, &states)
+                }
+                Event::ActivateWindowRequest(window_id) => {
+                    self.on_activate_window_request(&window_id)
+                }
+                Event::CurrentDesktopRequest(index) => self.on_current_desktop_request(index),
+                Event::CloseWindowRequest(window_id) => self.on_close_window_request(&window_id),
+                Event::WindowDesktopRequest(window_id, index) => {
+                    self.on_window_desktop_request(&window_id, index)
+                }
+                Event::UrgencyHintsChanged(window_id) => self.on_urgency_hints_changed(&window_id),
+                Event::ScreenChange => self.on_screen_change(),
+                Event::IpcRequest(request) => self.on_ipc_request(request),
+                Event::Hangup => self.on_hangup(),
+                Event::ChildExited => self.on_child_exited(),
             }
+            self.broadcast_events();
         }
         info!("Event loop exiting");
     }
@@ -309,6 +920,7 @@ impl Volan {
                 window_id
             );
             self.group_mut().focus(&window_id);
+            self.update_ewmh();
         }
     }
 
@@ -316,18 +928,554 @@ impl Volan {
         // We only receive an unmap notify event when the window is actually
         // unmapped by its application. When our layouts unmap windows, they
         // (should) do it by disabling event tracking first.
-        self.unmanage_window(window_id);
+        // Unlike a destroyed window, it may be mapped again by its client.
+        if self.unmanage_window(window_id) {
+            self.connection.clear_ewmh_window(window_id);
+        }
     }
 
     fn on_destroy_notify(&mut self, window_id: &WindowId) {
         self.unmanage_window(window_id);
     }
 
-    fn on_key_press(&mut self, key: KeyCombo) {
-        if let Some(handler) = self.keys.get(&key) {
-            if let Err(error) = (handler)(self) {
-                error!("Error running command for key command {:?}: {}", key, error);
+    /// Grabs the keys and mouse buttons again, as the keycodes of the bound
+    /// keysyms and the mods set by the lock keys may have changed.
+    fn on_keyboard_mapping_changed(&mut self) {
+        info!("Keyboard mapping changed, grabbing keys again");
+        // The button grabs are released before the lock mods are worked out
+        // again, as they were made with the old ones. The click to focus grabs
+        // are made again first so that mouse bindings replace them.
+        let click_to_focus: bool = self.settings.focus_policy == FocusPolicy::Click;
+        let windows: Vec<WindowId> = self
+            .workspaces
+            .iter()
+            .flat_map(|group| group.windows().cloned())
+            .collect();
+        for window_id in windows.iter() {
+            self.connection
+                .disable_window_mouse_events(window_id, &self.mouse);
+            if click_to_focus {
+                self.connection.disable_click_to_focus(window_id);
+            }
+        }
+        self.connection.refresh_keyboard_mapping();
+        self.regrab_keys();
+        for window_id in windows.iter() {
+            if click_to_focus {
+                self.connection.enable_click_to_focus(window_id);
+            }
+            self.connection
+                .enable_window_mouse_events(window_id, &self.mouse);
+        }
+    }
+
+    /// Replaces the key grabs on every window with those of the current key
+    /// bindings.
+    fn regrab_keys(&self) {
+        let windows: Vec<WindowId> = std::iter::once(*self.connection.root_window_id())
+            .chain(
+                self.workspaces
+                    .iter()
+                    .flat_map(|group| group.windows().cloned()),
+            )
+            .chain(
+                self.monitors
+                    .iter()
+                    .flat_map(|monitor| monitor.screen().docks())
+                    .map(|(window_id, _)| window_id),
+            )
+            .collect();
+        for window_id in windows.iter() {
+            self.connection.disable_window_key_events(window_id);
+            self.connection
+                .enable_window_key_events(window_id, &self.keys);
+        }
+    }
+
+    fn on_hangup(&mut self) {
+        info!("Received SIGHUP");
+        if let Err(error) = self.reload_config() {
+            error!("{}", error);
+        }
+    }
+
+    /// Reaps the programs which have exited, forgetting any which were started
+    /// by autostart without showing a window.
+    fn on_child_exited(&mut self) {
+        let reaped: Vec<u32> = signals::reap_children();
+        self.autostarted
+            .retain(|program| !reaped.contains(&program.pid));
+    }
+
+    /// Reads the config file again and applies the key bindings, gaps and
+    /// workspaces in it. Windows stay where they are.
+    ///
+    /// If the config can't be read, the current one is kept.
+    pub fn reload_config(&mut self) -> Result<()> {
+        info!("Reloading config");
+        let layout_names: Vec<&str> = self.layouts.iter().map(|layout| layout.name()).collect();
+        let parser: config::Parser =
+            config::Parser::load(self.settings.config_path.as_deref(), &layout_names)
+                .context("Could not reload config, keeping the current one")?;
+        if !parser.errors().is_empty() {
+            config::notify_errors(parser.errors());
+            return Err(format_err!(
+                "Not reloading config with {} error(s) in it, keeping the current one",
+                parser.errors().len()
+            ));
+        }
+        let (keys, workspaces): (KeyHandlers, Vec<WorkSpaceBuilder>) = load_bindings(&parser);
+
+        self.set_mode(None);
+        self.keys = keys;
+        self.sync_keyboard_grab();
+        self.regrab_keys();
+
+        let (innergaps, outergaps): (u32, u32) = parser.get_gaps();
+        for layout in self.layouts.iter_mut() {
+            layout.set_gaps(innergaps, outergaps);
+        }
+        for group in self.workspaces.iter_mut() {
+            group.set_gaps(innergaps, outergaps);
+        }
+
+        self.update_workspaces(workspaces);
+        self.update_ewmh();
+        set_log_rotation(parser.get_log_rotation());
+        self.autostart(&parser.get_autostart(), false);
+        self.events.push(WmEvent::ConfigReloaded);
+        Ok(())
+    }
+
+    /// Renames the existing groups to match the new workspace definitions and
+    /// adds groups for any extra definitions, putting the groups in the order
+    /// they're defined. Groups left over when there are fewer definitions are
+    /// kept at the end, so as not to lose their windows.
+    fn update_workspaces(&mut self, definitions: Vec<WorkSpaceBuilder>) {
+        let old_names: Vec<String> = self
+            .workspaces
+            .iter()
+            .map(|group| group.name().to_string())
+            .collect();
+        let new_names: Vec<String> = definitions
+            .iter()
+            .map(|definition| definition.name().to_string())
+            .collect();
+        let (renames, added): (Vec<String>, Vec<String>) = rename_groups(&old_names, &new_names);
+        let renamed = |name: &str| -> String {
+            old_names
+                .iter()
+                .position(|old| old == name)
+                .map(|index| renames[index].clone())
+                .unwrap_or_else(|| name.to_string())
+        };
+
+        for monitor in self.monitors.iter_mut() {
+            let workspace: Option<String> = monitor.workspace().map(&renamed);
+            monitor.set_workspace(workspace);
+        }
+        for (group, name) in self.workspaces.iter_mut().zip(renames.iter()) {
+            if group.name() != name {
+                info!("Renaming workspace {} to {}", group.name(), name);
+                group.rename(name.as_str());
+            }
+        }
+
+        let focused: String = self.group().name().to_string();
+        let mut groups: Vec<WorkSpace> = Vec::new();
+        while !self.workspaces.is_empty() {
+            groups.push(self.workspaces.remove(|_| true));
+        }
+        for definition in definitions {
+            if added.iter().any(|name| name == definition.name()) {
+                info!("Adding workspace {}", definition.name());
+                groups.push(definition.build(
+                    self.connection.clone(),
+                    self.events.clone(),
+                    self.layouts.clone(),
+                    self.settings.borders,
+                ));
+            }
+        }
+        groups.sort_by_key(|group| {
+            new_names
+                .iter()
+                .position(|name| name == group.name())
+                .unwrap_or(new_names.len())
+        });
+        self.workspaces = Stack::from(groups);
+        self.workspaces.focus(|group| group.name() == focused);
+
+        // Outputs which had no group to show can show one of the new ones.
+        let outputs: Vec<Output> = self.connection.get_outputs();
+        self.configure_monitors(outputs);
+    }
+
+    fn on_screen_change(&mut self) {
+        info!("Screen configuration changed");
+        let outputs: Vec<Output> = self.connection.get_outputs();
+        self.configure_monitors(outputs);
+    }
+
+    fn on_ipc_request(&mut self, request: ipc::Request) {
+        info!("Received IPC command: {}", request.message);
+        let previous_focus: Option<WindowId> = self.group().focused_window().cloned();
+        let reply: ipc::Reply = match request.parse() {
+            Ok(ipc::Message::Command(command)) => match (command)(self) {
+                Ok(()) => ipc::Reply::ok(),
+                Err(error) => ipc::Reply::error(error.to_string()),
+            },
+            Ok(ipc::Message::Subscribe) => {
+                if let Some(ipc) = &self.ipc {
+                    ipc.subscribe(request.client);
+                }
+                ipc::Reply::ok()
+            }
+            Ok(ipc::Message::GetTree) => match serde_json::to_value(tree::Tree::new(self)) {
+                Ok(tree) => ipc::Reply::data(tree),
+                Err(error) => ipc::Reply::error(error.to_string()),
+            },
+            Err(error) => ipc::Reply::error(error.to_string()),
+        };
+        if let Some(ipc) = &self.ipc {
+            ipc.reply(request.client, &reply);
+        }
+        self.warp_pointer_to_focus(previous_focus);
+    }
+
+    fn on_window_state_request(
+        &mut self,
+        window_id: &WindowId,
+        action: StateAction,
+        states: &[WindowState],
+    ) {
+        let group: &mut WorkSpace = match self.workspaces.iter_mut().find(|g| g.contains(window_id))
+        {
+            Some(group) => group,
+            None => return,
+        };
+        if states.contains(&WindowState::Fullscreen) {
+            let fullscreen: bool = action.apply(group.is_fullscreen(window_id));
+            group.set_fullscreen(window_id, fullscreen);
+            self.update_ewmh();
+        }
+        if states.contains(&WindowState::DemandsAttention) {
+            // The border changes once the property has, along with WM_HINTS.
+            let demands_attention: bool = action.apply(
+                self.connection
+                    .get_window_states(window_id)
+                    .contains(&WindowState::DemandsAttention),
+            );
+            self.connection.set_window_state(
+                window_id,
+                WindowState::DemandsAttention,
+                demands_attention,
+            );
+        }
+    }
+
+    /// Shows whether a window is asking for attention, after its WM_HINTS or
+    /// _NET_WM_STATE has changed.
+    fn on_urgency_hints_changed(&mut self, window_id: &WindowId) {
+        if let Some(group) = self.workspaces.iter_mut().find(|g| g.contains(window_id)) {
+            group.set_urgent(window_id, self.connection.is_urgent(window_id));
+        }
+    }
+
+    /// Returns the name of the group with the given EWMH desktop index.
+    fn group_name_at(&self, index: u32) -> Option<String> {
+        self.workspaces
+            .iter()
+            .nth(index as usize)
+            .map(|group| group.name().to_string())
+    }
+
+    /// Returns the name of the group containing the window.
+    fn group_name_of(&self, window_id: &WindowId) -> Option<String> {
+        self.workspaces
+            .iter()
+            .find(|group| group.contains(window_id))
+            .map(|group| group.name().to_string())
+    }
+
+    fn on_activate_window_request(&mut self, window_id: &WindowId) {
+        if let Some(name) = self.group_name_of(window_id) {
+            info!("Activating window {} in group {}", window_id, name);
+            self.switch_group(name);
+            self.group_mut().focus(window_id);
+            self.update_ewmh();
+        }
+    }
+
+    fn on_current_desktop_request(&mut self, index: u32) {
+        match self.group_name_at(index) {
+            Some(name) => self.switch_group(name),
+            None => error!("Asked to switch to non-existent desktop {}", index),
+        }
+    }
+
+    fn on_close_window_request(&mut self, window_id: &WindowId) {
+        if self.is_window_managed(window_id) {
+            self.connection.close_window(window_id);
+        }
+    }
+
+    fn on_window_desktop_request(&mut self, window_id: &WindowId, index: u32) {
+        let (from, to) = match (self.group_name_of(window_id), self.group_name_at(index)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => {
+                error!("Cannot move window {} to desktop {}", window_id, index);
+                return;
+            }
+        };
+        if from == self.group().name() {
+            self.group_mut().focus(window_id);
+            self.move_focused_to_group(to);
+        } else if from != to {
+            // The window isn't in the focused group, so it can be moved
+            // without disturbing the focus. It only needs to be hidden if its
+            // group is visible on another monitor.
+            let mut visible: bool = false;
+            let mut removed: Option<RemovedWindow> = None;
+            if let Some(group) = self.workspaces.iter_mut().find(|g| g.name() == from) {
+                removed = Some(group.remove_window(window_id));
+                visible = group.is_active();
+            }
+            if visible {
+                self.connection.disable_window_tracking(window_id);
+                self.connection.unmap_window(window_id);
+                self.connection.enable_window_tracking(window_id);
+            }
+            if let (Some(group), Some(removed)) =
+                (self.workspaces.iter_mut().find(|g| g.name() == to), removed)
+            {
+                group.add_removed_window(removed);
+            }
+            self.update_ewmh();
+        }
+    }
+
+    fn on_enter_notify(&mut self, window_id: WindowId) {
+        if self.settings.focus_policy != FocusPolicy::Click {
+            self.focus_if_unfocused(&window_id);
+        }
+    }
+
+    fn on_button_press(
+        &mut self,
+        window_id: WindowId,
+        combo: MouseCombo,
+        position: PointerPosition,
+    ) {
+        match self.mouse.get(&combo) {
+            Some(action) if self.is_window_visible(&window_id) => {
+                debug!("Starting {:?} of window {}", action, window_id);
+                self.focus_if_unfocused(&window_id);
+                self.drag = Some(Drag {
+                    window_id,
+                    action,
+                    last_position: position,
+                });
+            }
+            Some(_) => {}
+            None => {
+                if self.settings.focus_policy == FocusPolicy::Click {
+                    self.focus_if_unfocused(&window_id);
+                }
+            }
+        }
+    }
+
+    fn on_motion_notify(&mut self, position: PointerPosition) {
+        let (window_id, action, last_position) = match &self.drag {
+            Some(drag) => (drag.window_id, drag.action, drag.last_position),
+            None => return,
+        };
+        let (dx, dy): (i16, i16) = (position.0 - last_position.0, position.1 - last_position.1);
+        let floating: bool = self.group().is_floating(&window_id);
+        match action {
+            MouseAction::Move if floating => self.group_mut().move_floating(&window_id, dx, dy),
+            MouseAction::Resize if floating => self.group_mut().resize_floating(&window_id, dx, dy),
+            MouseAction::Resize => {
+                if dx != 0 {
+                    self.group_mut().resize_master(dx);
+                }
+            }
+            // Tiled windows only move when they're dropped.
+            MouseAction::Move => {}
+        }
+        if let Some(drag) = self.drag.as_mut() {
+            drag.last_position = position;
+        }
+    }
+
+    fn on_button_release(&mut self) {
+        let drag: Drag = match self.drag.take() {
+            Some(drag) => drag,
+            None => return,
+        };
+        debug!("Finished {:?} of window {}", drag.action, drag.window_id);
+        if drag.action == MouseAction::Move && !self.group().is_floating(&drag.window_id) {
+            // Swap the dragged window with the one it was dropped on.
+            if let Some(target) = self.connection.window_under_pointer() {
+                if target != drag.window_id
+                    && self.group().contains(&target)
+                    && self.group().contains(&drag.window_id)
+                {
+                    self.group_mut().swap_windows(&drag.window_id, &target);
+                }
+            }
+        }
+    }
+
+    /// Returns whether the window is in a group shown on any monitor.
+    fn is_window_visible(&self, window_id: &WindowId) -> bool {
+        self.workspaces
+            .iter()
+            .any(|group| group.is_active() && group.contains(window_id))
+    }
+
+    /// Focuses a window in a visible group, unless it already has the focus.
+    /// If the window is on another monitor, that monitor's group is focused.
+    fn focus_if_unfocused(&mut self, window_id: &WindowId) {
+        if !self.is_window_visible(window_id) {
+            return;
+        }
+        if !self.group().contains(window_id) {
+            if let Some(name) = self.group_name_of(window_id) {
+                self.switch_group(name);
+            }
+        }
+        if self.group().focused_window() != Some(window_id) {
+            self.group_mut().focus(window_id);
+            self.update_ewmh();
+        }
+    }
+
+    /// With strict focus, moves the pointer onto the focused window if the
+    /// focus was changed by something other than the pointer.
+    fn warp_pointer_to_focus(&self, previous: Option<WindowId>) {
+        if self.settings.focus_policy != FocusPolicy::Strict {
+            return;
+        }
+        if let Some(focused) = self.group().focused_window() {
+            if Some(*focused) != previous {
+                self.connection.warp_pointer_to(focused);
+                self.connection.discard_enter_events();
+            }
+        }
+    }
+
+    /// Sends queued events to subscribed IPC clients.
+    fn broadcast_events(&self) {
+        let events: Vec<WmEvent> = self.events.drain();
+        if let Some(ipc) = &self.ipc {
+            for event in events.iter() {
+                ipc.broadcast(event);
             }
         }
     }
+
+    /// Switches to a named keymap mode, or back to the usual keymap.
+    pub fn set_mode(&mut self, mode: Option<String>) {
+        if self.keys.mode() == mode.as_deref() {
+            return;
+        }
+        if let Some(name) = &mode {
+            if !self.keys.has_mode(name) {
+                error!("Asked to enter non-existent mode: {}", name);
+                return;
+            }
+        }
+        info!("Switching to mode {:?}", mode);
+        self.keys.set_mode(mode.clone());
+        self.events.push(WmEvent::ModeChanged { mode });
+        self.sync_keyboard_grab();
+    }
+
+    /// Grabs the whole keyboard while a sequence or mode is in progress, so
+    /// that we see keys which aren't grabbed in the usual keymap.
+    fn sync_keyboard_grab(&mut self) {
+        let wanted: bool = self.keys.wants_keyboard();
+        if wanted == self.keyboard_grabbed {
+            return;
+        }
+        if wanted {
+            self.keyboard_grabbed = self.connection.grab_keyboard();
+        } else {
+            self.connection.ungrab_keyboard();
+            self.keyboard_grabbed = false;
+        }
+    }
+
+    /// Handles a key press or release, given each way it could be bound.
+    fn on_key(&mut self, keys: &[KeyCombo]) {
+        let key: Option<&KeyCombo> = keys.first();
+        match self.keys.press_any(keys) {
+            KeyAction::Run(handler) => {
+                let previous_focus: Option<WindowId> = self.group().focused_window().cloned();
+                if let Err(error) = (handler)(self) {
+                    error!("Error running command for key command {:?}: {}", key, error);
+                }
+                self.warp_pointer_to_focus(previous_focus);
+            }
+            KeyAction::Pending => debug!("Waiting for the next key after {:?}", key),
+            KeyAction::Unbound if !key.is_some_and(|key| key.release) => {
+                debug!("No binding for key {:?}", key)
+            }
+            KeyAction::Unbound => {}
+        }
+        self.sync_keyboard_grab();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::rename_groups;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_rename_groups_unchanged() {
+        let old: Vec<String> = names(&["1", "2", "3"]);
+        assert_eq!(rename_groups(&old, &old), (old.clone(), vec![]));
+    }
+
+    #[test]
+    fn test_rename_groups_shrinking() {
+        assert_eq!(
+            rename_groups(&names(&["1", "2", "3"]), &names(&["2", "3"])),
+            (names(&["1", "2", "3"]), vec![])
+        );
+        assert_eq!(
+            rename_groups(&names(&["1", "2", "3"]), &names(&["web", "3"])),
+            (names(&["web", "2", "3"]), vec![])
+        );
+    }
+
+    #[test]
+    fn test_rename_groups_growing() {
+        assert_eq!(
+            rename_groups(&names(&["1", "2"]), &names(&["1", "2", "3", "4"])),
+            (names(&["1", "2"]), names(&["3", "4"]))
+        );
+        assert_eq!(
+            rename_groups(&names(&["1", "2"]), &names(&["a", "2", "b"])),
+            (names(&["a", "2"]), names(&["b"]))
+        );
+    }
+
+    #[test]
+    fn test_rename_groups_reordering() {
+        assert_eq!(
+            rename_groups(&names(&["1", "2", "3"]), &names(&["3", "1", "2"])),
+            (names(&["1", "2", "3"]), vec![])
+        );
+        assert_eq!(
+            rename_groups(&names(&["1", "2", "3"]), &names(&["3", "a", "1"])),
+            (names(&["1", "a", "3"]), vec![])
+        );
+    }
 }
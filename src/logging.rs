@@ -0,0 +1,401 @@
+//! The log file, which is rotated so that it doesn't grow forever.
+//!
+//! The file is kept in a global so that how it's rotated, which is read from
+//! the config file, can be set after the logger is installed and changed when
+//! the config is reloaded.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::UNIX_EPOCH;
+
+use flate2::{write::GzEncoder, Compression};
+
+use crate::settings::{LogRotation, Rotate};
+
+static LOG_FILE: Mutex<Option<LogFile>> = Mutex::new(None);
+
+/// A year and a day of the year, in local time.
+type Day = (i32, i32);
+
+fn day_of(time: time::Tm) -> Day {
+    (time.tm_year, time.tm_yday)
+}
+
+/// Writes to the log file opened by `open()`.
+pub struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        match LOG_FILE
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .as_mut()
+        {
+            Some(log_file) => log_file.write(buffer),
+            None => Ok(buffer.len()),
+        }
+    }
+
+    /// fern flushes after each message, which is when the file may be
+    /// rotated.
+    fn flush(&mut self) -> io::Result<()> {
+        match LOG_FILE
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .as_mut()
+        {
+            Some(log_file) => log_file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Opens the log file for appending, rotating it with the default settings
+/// until `set_log_rotation()` is called.
+pub fn open(path: &Path) -> io::Result<LogWriter> {
+    let log_file: LogFile = LogFile::open(path.to_path_buf(), LogRotation::default())?;
+    *LOG_FILE.lock().unwrap_or_else(|error| error.into_inner()) = Some(log_file);
+    Ok(LogWriter)
+}
+
+/// Changes how the log file is rotated, from the next message on.
+pub fn set_log_rotation(rotation: LogRotation) {
+    if let Some(log_file) = LOG_FILE
+        .lock()
+        .unwrap_or_else(|error| error.into_inner())
+        .as_mut()
+    {
+        log_file.rotation = rotation;
+    }
+}
+
+struct LogFile {
+    path: PathBuf,
+    file: BufWriter<File>,
+    /// How many bytes are in the file.
+    size: u64,
+    /// The day the file was started, or last written to before we opened it.
+    day: Day,
+    rotation: LogRotation,
+    /// Whether part of a message has been written since the last flush. The
+    /// file is only rotated between messages.
+    mid_message: bool,
+    /// The thread compressing the last log to be rotated, as that can take
+    /// a while and messages are written from the event loop.
+    compressing: Option<JoinHandle<()>>,
+}
+
+impl LogFile {
+    fn open(path: PathBuf, rotation: LogRotation) -> io::Result<LogFile> {
+        let file: File = OpenOptions::new().create(true).append(true).open(&path)?;
+        let metadata: fs::Metadata = file.metadata()?;
+        let day: Day = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|since_epoch| {
+                day_of(time::at(time::Timespec::new(
+                    since_epoch.as_secs() as i64,
+                    0,
+                )))
+            })
+            .unwrap_or_else(|| day_of(time::now()));
+        Ok(LogFile {
+            path,
+            file: BufWriter::new(file),
+            size: metadata.len(),
+            day,
+            rotation,
+            mid_message: false,
+            compressing: None,
+        })
+    }
+
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        if !self.mid_message {
+            self.mid_message = true;
+            self.rotate_if_due();
+        }
+        let written: usize = self.file.write(buffer)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.mid_message = false;
+        self.file.flush()
+    }
+
+    fn rotate_if_due(&mut self) {
+        let today: Day = day_of(time::now());
+        let due: bool = match self.rotation.rotate {
+            Rotate::Never => false,
+            Rotate::Size => self.size > self.rotation.max_size,
+            Rotate::Daily => self.day != today,
+        };
+        if !due {
+            return;
+        }
+        if let Err(error) = self.rotate() {
+            // This can't go to the log, which is what's being rotated.
+            eprintln!("Could not rotate log file {:?}: {}", self.path, error);
+            // Carry on with the same file, trying again once it's grown by
+            // as much again or the next day, rather than on every message.
+            self.size = 0;
+            self.day = today;
+        }
+    }
+
+    /// Moves the log to `.1`, shifting the older logs up by one and dropping
+    /// any past the number to keep, and starts a new log.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        // The old logs can't be moved while one of them is being compressed.
+        self.wait_for_compression();
+        let keep: usize = self.rotation.keep;
+
+        // There may be more than are kept if `keep` has been lowered.
+        let mut index: usize = keep.max(1);
+        while self.old_log(index).is_some() {
+            while let Some(old_log) = self.old_log(index) {
+                fs::remove_file(old_log)?;
+            }
+            index += 1;
+        }
+        for index in (1..keep).rev() {
+            while let Some(old_log) = self.old_log(index) {
+                let compressed: bool = old_log.extension().is_some_and(|ext| ext == "gz");
+                fs::rename(&old_log, self.old_log_path(index + 1, compressed))?;
+            }
+        }
+
+        if keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            fs::rename(&self.path, self.old_log_path(1, false))?;
+        }
+        *self = LogFile::open(self.path.clone(), self.rotation)?;
+        self.mid_message = true;
+
+        if keep > 0 && self.rotation.compress {
+            let old_log: PathBuf = self.old_log_path(1, false);
+            self.compressing = Some(thread::spawn(move || {
+                if let Err(error) = compress(&old_log) {
+                    eprintln!("Could not compress log file {:?}: {}", old_log, error);
+                }
+            }));
+        }
+        Ok(())
+    }
+
+    fn wait_for_compression(&mut self) {
+        if let Some(compressing) = self.compressing.take() {
+            // A panic has already been reported by the panic hook.
+            let _ = compressing.join();
+        }
+    }
+
+    /// Returns the path of an old log, whether or not it's compressed.
+    fn old_log_path(&self, index: usize, compressed: bool) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        if compressed {
+            path.push(".gz");
+        }
+        PathBuf::from(path)
+    }
+
+    /// Returns the path of an old log if there is one with that number.
+    fn old_log(&self, index: usize) -> Option<PathBuf> {
+        [false, true]
+            .iter()
+            .map(|&compressed| self.old_log_path(index, compressed))
+            .find(|path| path.exists())
+    }
+}
+
+/// Replaces a file with a gzipped copy of it.
+///
+/// The copy is written under another name first, so that if we're stopped
+/// part way through there's never a truncated `.gz` next to the original.
+fn compress(path: &Path) -> io::Result<()> {
+    let mut compressed_path = path.to_path_buf().into_os_string();
+    compressed_path.push(".gz");
+    let mut partial_path = compressed_path.clone();
+    partial_path.push(".part");
+    let mut encoder = GzEncoder::new(File::create(&partial_path)?, Compression::default());
+    io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?;
+    fs::rename(partial_path, compressed_path)?;
+    fs::remove_file(path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::LogFile;
+    use crate::settings::{LogRotation, Rotate};
+    use flate2::read::GzDecoder;
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::path::{Path, PathBuf};
+
+    /// Returns an empty directory for a test's logs.
+    fn log_dir(test: &str) -> PathBuf {
+        let dir: PathBuf =
+            std::env::temp_dir().join(format!("volan-log-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn rotation(keep: usize, compress: bool) -> LogRotation {
+        LogRotation {
+            rotate: Rotate::Size,
+            max_size: 10,
+            keep,
+            compress,
+        }
+    }
+
+    fn log(log_file: &mut LogFile, message: &str) {
+        log_file.write(message.as_bytes()).unwrap();
+        log_file.flush().unwrap();
+    }
+
+    fn read(path: &Path) -> String {
+        let mut text = String::new();
+        if path.extension().is_some_and(|ext| ext == "gz") {
+            GzDecoder::new(fs::File::open(path).unwrap())
+                .read_to_string(&mut text)
+                .unwrap();
+        } else {
+            fs::File::open(path)
+                .unwrap()
+                .read_to_string(&mut text)
+                .unwrap();
+        }
+        text
+    }
+
+    /// Returns the names of the files in a directory, in order.
+    fn files(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_rotate_on_size() {
+        let dir: PathBuf = log_dir("size");
+        let path: PathBuf = dir.join("volan.log");
+        let mut log_file = LogFile::open(path.clone(), rotation(2, false)).unwrap();
+        log(&mut log_file, "first message\n");
+        log(&mut log_file, "second message\n");
+        log(&mut log_file, "third message\n");
+        assert_eq!(files(&dir), vec!["volan.log", "volan.log.1", "volan.log.2"]);
+        assert_eq!(read(&path), "third message\n");
+        assert_eq!(read(&dir.join("volan.log.1")), "second message\n");
+        assert_eq!(read(&dir.join("volan.log.2")), "first message\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_keep_lowered() {
+        let dir: PathBuf = log_dir("lowered");
+        let path: PathBuf = dir.join("volan.log");
+        for name in [
+            "volan.log.1",
+            "volan.log.2.gz",
+            "volan.log.3",
+            "volan.log.4.gz",
+        ]
+        .iter()
+        {
+            fs::write(dir.join(name), name).unwrap();
+        }
+        fs::write(&path, "the current log\n").unwrap();
+        let mut log_file = LogFile::open(path.clone(), rotation(2, false)).unwrap();
+        log(&mut log_file, "a new message\n");
+        assert_eq!(files(&dir), vec!["volan.log", "volan.log.1", "volan.log.2"]);
+        assert_eq!(read(&dir.join("volan.log.1")), "the current log\n");
+        assert_eq!(read(&dir.join("volan.log.2")), "volan.log.1");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_keep_none() {
+        let dir: PathBuf = log_dir("none");
+        let path: PathBuf = dir.join("volan.log");
+        fs::write(dir.join("volan.log.1.gz"), "").unwrap();
+        fs::write(&path, "the current log\n").unwrap();
+        let mut log_file = LogFile::open(path.clone(), rotation(0, true)).unwrap();
+        log(&mut log_file, "a new message\n");
+        log_file.wait_for_compression();
+        assert_eq!(files(&dir), vec!["volan.log"]);
+        assert_eq!(read(&path), "a new message\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_mixed_compression() {
+        let dir: PathBuf = log_dir("mixed");
+        let path: PathBuf = dir.join("volan.log");
+        fs::write(dir.join("volan.log.1"), "uncompressed\n").unwrap();
+        let mut compressed = flate2::write::GzEncoder::new(
+            fs::File::create(dir.join("volan.log.2.gz")).unwrap(),
+            flate2::Compression::default(),
+        );
+        compressed.write_all(b"compressed\n").unwrap();
+        compressed.finish().unwrap();
+        fs::write(&path, "the current log\n").unwrap();
+
+        let mut log_file = LogFile::open(path.clone(), rotation(3, true)).unwrap();
+        log(&mut log_file, "a new message\n");
+        log_file.wait_for_compression();
+        assert_eq!(
+            files(&dir),
+            vec![
+                "volan.log",
+                "volan.log.1.gz",
+                "volan.log.2",
+                "volan.log.3.gz"
+            ]
+        );
+        assert_eq!(read(&dir.join("volan.log.1.gz")), "the current log\n");
+        assert_eq!(read(&dir.join("volan.log.2")), "uncompressed\n");
+        assert_eq!(read(&dir.join("volan.log.3.gz")), "compressed\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rotate_daily() {
+        let dir: PathBuf = log_dir("daily");
+        let path: PathBuf = dir.join("volan.log");
+        let mut rotation: LogRotation = rotation(5, false);
+        rotation.rotate = Rotate::Daily;
+        let mut log_file = LogFile::open(path.clone(), rotation).unwrap();
+        log(
+            &mut log_file,
+            "a long message which is well over max_size\n",
+        );
+        log(&mut log_file, "another message on the same day\n");
+        assert_eq!(files(&dir), vec!["volan.log"]);
+
+        // As if the last message was written yesterday.
+        log_file.day.1 -= 1;
+        log(&mut log_file, "a message the next day\n");
+        assert_eq!(files(&dir), vec!["volan.log", "volan.log.1"]);
+        assert_eq!(read(&path), "a message the next day\n");
+        assert_eq!(
+            read(&dir.join("volan.log.1")),
+            "a long message which is well over max_size\nanother message on the same day\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,67 @@
+use crate::screen::Screen;
+use crate::x::{Output, PointerPosition};
+use crate::Viewport;
+
+/// A physical output, which shows at most one workspace at a time.
+pub struct Monitor {
+    name: String,
+    /// The area of the root window covered by the output.
+    geometry: Viewport,
+    /// The docks placed on this output.
+    screen: Screen,
+    /// The name of the workspace shown on the output, if there are more
+    /// outputs than workspaces.
+    workspace: Option<String>,
+}
+
+impl Monitor {
+    pub fn new(output: Output, workspace: Option<String>) -> Monitor {
+        Monitor {
+            name: output.name,
+            geometry: output.geometry,
+            screen: Screen::default(),
+            workspace,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the whole output, ignoring docks.
+    pub fn geometry(&self) -> Viewport {
+        self.geometry
+    }
+
+    /// Returns the part of the output which isn't reserved by docks.
+    pub fn viewport(&self, screen_width: u32, screen_height: u32) -> Viewport {
+        self.screen
+            .viewport(&self.geometry, screen_width, screen_height)
+    }
+
+    pub fn screen(&self) -> &Screen {
+        &self.screen
+    }
+
+    pub fn screen_mut(&mut self) -> &mut Screen {
+        &mut self.screen
+    }
+
+    pub fn workspace(&self) -> Option<&str> {
+        self.workspace.as_deref()
+    }
+
+    pub fn set_workspace(&mut self, workspace: Option<String>) {
+        self.workspace = workspace;
+    }
+
+    /// Returns whether a point on the root window lies on this output.
+    pub fn contains(&self, (x, y): PointerPosition) -> bool {
+        let (x, y) = (i64::from(x), i64::from(y));
+        let (left, top) = (i64::from(self.geometry.x), i64::from(self.geometry.y));
+        x >= left
+            && y >= top
+            && x < left + i64::from(self.geometry.width)
+            && y < top + i64::from(self.geometry.height)
+    }
+}
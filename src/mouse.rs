@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+
+use crate::keys::{ModKey, ModMask};
+
+/// What dragging a window with a mouse binding does.
+#[derive(Copy, Clone, Debug, PartialEq, EnumString)]
+pub enum MouseAction {
+    /// Drag a floating window around. Tiled windows swap places with the
+    /// window they are dropped on.
+    Move,
+    /// Drag to resize a floating window. For tiled windows this resizes the
+    /// master area.
+    Resize,
+}
+
+/// A pointer button, numbered as in X (1 is the left button).
+pub type Button = u8;
+
+/// A combination of zero or more mods and a pointer button.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct MouseCombo {
+    pub mod_mask: ModMask,
+    pub button: Button,
+}
+
+impl MouseCombo {
+    fn new(mods: &[ModKey], button: Button) -> MouseCombo {
+        MouseCombo {
+            mod_mask: ModKey::combine(mods),
+            button,
+        }
+    }
+}
+
+pub struct MouseHandlers {
+    hashmap: HashMap<MouseCombo, MouseAction>,
+}
+
+impl MouseHandlers {
+    pub fn mouse_combos(&self) -> Vec<&MouseCombo> {
+        self.hashmap.keys().collect()
+    }
+
+    pub fn get(&self, mouse_combo: &MouseCombo) -> Option<MouseAction> {
+        self.hashmap.get(mouse_combo).cloned()
+    }
+}
+
+impl From<Vec<(Vec<ModKey>, Button, MouseAction)>> for MouseHandlers {
+    fn from(handlers: Vec<(Vec<ModKey>, Button, MouseAction)>) -> MouseHandlers {
+        let mut hashmap = HashMap::new();
+        for (modkeys, button, action) in handlers {
+            hashmap.insert(MouseCombo::new(&modkeys, button), action);
+        }
+        MouseHandlers { hashmap }
+    }
+}
@@ -3,6 +3,9 @@ use {
     std::{cell::RefCell, cmp},
 };
 
+/// The space a dock reserves at the (left, right, top, bottom) screen edges.
+pub type Reserved = (u32, u32, u32, u32);
+
 #[derive(Default)]
 pub struct Screen {
     vec: RefCell<Vec<Dock>>,
@@ -21,9 +24,28 @@ impl Screen {
         self.vec.borrow_mut().retain(|d| &d.window_id != window_id);
     }
 
-    /// Figure out the usable area of the screen based on the STRUT_PARTIAL of
-    /// all docks.
-    pub fn viewport(&self, screen_width: u32, screen_height: u32) -> Viewport {
+    /// Returns each dock's window along with the space it reserves, if it set
+    /// a STRUT_PARTIAL.
+    pub fn docks(&self) -> Vec<(WindowId, Option<Reserved>)> {
+        self.vec
+            .borrow()
+            .iter()
+            .map(|d| {
+                let strut = d
+                    .strut_partial
+                    .as_ref()
+                    .map(|s| (s.left(), s.right(), s.top(), s.bottom()));
+                (d.window_id, strut)
+            })
+            .collect()
+    }
+
+    /// Figure out the usable area of an output based on the STRUT_PARTIAL of
+    /// its docks.
+    ///
+    /// Struts are measured from the edges of the whole screen, so its size is
+    /// needed to work out how far each one reaches into the output.
+    pub fn viewport(&self, output: &Viewport, screen_width: u32, screen_height: u32) -> Viewport {
         let (left, right, top, bottom): (u32, u32, u32, u32) = self
             .vec
             .borrow()
@@ -39,11 +61,16 @@ impl Screen {
                     cmp::max(bottom, s.bottom()),
                 )
             });
+        let left: u32 = left.saturating_sub(output.x);
+        let right: u32 = right.saturating_sub(screen_width.saturating_sub(output.x + output.width));
+        let top: u32 = top.saturating_sub(output.y);
+        let bottom: u32 =
+            bottom.saturating_sub(screen_height.saturating_sub(output.y + output.height));
         let viewport = Viewport {
-            x: left,
-            y: top,
-            width: screen_width - left - right,
-            height: screen_height - top - bottom,
+            x: output.x + left,
+            y: output.y + top,
+            width: output.width.saturating_sub(left + right),
+            height: output.height.saturating_sub(top + bottom),
         };
         debug!("Calculated Viewport as {:?}", viewport);
         viewport
@@ -0,0 +1,105 @@
+//! Behavioural options for the window manager, read from the config file.
+
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+/// How the pointer affects which window has the input focus.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FocusPolicy {
+    /// Windows are focused by clicking on them (or from the keyboard).
+    #[default]
+    Click,
+    /// Windows are focused when the pointer enters them. Focus stays put when
+    /// the pointer moves over the root window or the gaps.
+    Sloppy,
+    /// As `Sloppy`, but the pointer is also warped to windows focused from the
+    /// keyboard, so that the pointer is always over the focused window.
+    Strict,
+}
+
+/// What happens when switching to a workspace which is already shown on
+/// another monitor.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkspaceSwitching {
+    /// Move the focus over to the other monitor, as in i3.
+    #[default]
+    Focus,
+    /// Swap the workspaces of the two monitors, bringing the workspace to the
+    /// focused monitor, as in xmonad.
+    Swap,
+}
+
+/// The border drawn around each window. Colours are X pixel values, which
+/// are `0xRRGGBB` on TrueColor displays.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Borders {
+    pub width: u32,
+    pub focused: u32,
+    pub unfocused: u32,
+    /// For windows asking for attention.
+    pub urgent: u32,
+    /// For unfocused floating windows.
+    pub floating: u32,
+}
+
+impl Default for Borders {
+    fn default() -> Self {
+        Borders {
+            width: 2,
+            focused: 0x005294e2,
+            unfocused: 0x002f343f,
+            urgent: 0x00e06c75,
+            floating: 0x008fbcbb,
+        }
+    }
+}
+
+/// When the log file is set aside and a new one started.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rotate {
+    Never,
+    /// Once the log has grown past a size.
+    #[default]
+    Size,
+    /// At the first message of each day.
+    Daily,
+}
+
+/// How the log file is kept from growing forever. Old logs are numbered
+/// `volan.log.1`, `volan.log.2` and so on, from the newest.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LogRotation {
+    pub rotate: Rotate,
+    /// The size in bytes past which the log is rotated, for `Rotate::Size`.
+    pub max_size: u64,
+    /// How many old logs to keep.
+    pub keep: usize,
+    /// Whether to gzip old logs.
+    pub compress: bool,
+}
+
+impl Default for LogRotation {
+    fn default() -> Self {
+        LogRotation {
+            rotate: Rotate::Size,
+            max_size: 10 * 1024 * 1024,
+            keep: 5,
+            compress: true,
+        }
+    }
+}
+
+/// Options which are fixed for the lifetime of a `Volan` instance.
+#[derive(Clone, Debug, Default)]
+pub struct Settings {
+    pub focus_policy: FocusPolicy,
+    pub workspace_switching: WorkspaceSwitching,
+    pub borders: Borders,
+    /// The config file given on the command line, which is read again on
+    /// reload. If unset, the one in the XDG config directory is used.
+    pub config_path: Option<PathBuf>,
+}
@@ -0,0 +1,118 @@
+//! Turns SIGHUP and SIGCHLD into something the event loop can poll for.
+//!
+//! The signal handler only writes the signal number to a pipe, which is about
+//! all that is safe to do in a handler. The event loop polls the other end of
+//! the pipe alongside the X connection and the control socket.
+
+use std::os::raw::{c_int, c_void};
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+use failure::format_err;
+
+use crate::Result;
+
+/// The write end of the pipe, for the signal handler.
+static SIGNAL_WRITE_FD: AtomicI32 = AtomicI32::new(-1);
+
+extern "C" fn on_signal(signal: c_int) {
+    let fd: RawFd = SIGNAL_WRITE_FD.load(Ordering::Relaxed);
+    if fd >= 0 {
+        let byte: u8 = signal as u8;
+        // If the pipe is full then the signal is already pending.
+        unsafe { libc::write(fd, &byte as *const u8 as *const c_void, 1) };
+    }
+}
+
+/// The signals received since they were last taken.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Received {
+    /// SIGHUP, asking for the config to be read again.
+    pub hangup: bool,
+    /// SIGCHLD, as a child process has exited and needs to be reaped.
+    pub child_exited: bool,
+}
+
+/// Notices SIGHUP and SIGCHLD being sent to the window manager.
+pub struct Signals {
+    read_fd: RawFd,
+}
+
+impl Signals {
+    /// Installs the SIGHUP and SIGCHLD handlers.
+    pub fn install() -> Result<Signals> {
+        let mut fds: [c_int; 2] = [-1; 2];
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) } != 0 {
+            return Err(format_err!(
+                "Could not create signal pipe: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+        SIGNAL_WRITE_FD.store(fds[1], Ordering::Relaxed);
+
+        for &(signal, name) in [(libc::SIGHUP, "SIGHUP"), (libc::SIGCHLD, "SIGCHLD")].iter() {
+            unsafe {
+                let mut action: libc::sigaction = std::mem::zeroed();
+                action.sa_sigaction = on_signal as extern "C" fn(c_int) as libc::sighandler_t;
+                action.sa_flags = libc::SA_RESTART | libc::SA_NOCLDSTOP;
+                libc::sigemptyset(&mut action.sa_mask);
+                if libc::sigaction(signal, &action, std::ptr::null_mut()) != 0 {
+                    return Err(format_err!(
+                        "Could not install {} handler: {}",
+                        name,
+                        std::io::Error::last_os_error()
+                    ));
+                }
+            }
+        }
+        Ok(Signals { read_fd: fds[0] })
+    }
+
+    /// Returns the file descriptor that becomes readable on a signal.
+    pub fn fd(&self) -> RawFd {
+        self.read_fd
+    }
+
+    /// Returns the signals received since the last call, however many times
+    /// each was sent.
+    pub fn take(&self) -> Received {
+        let mut buffer = [0u8; 64];
+        let mut received: Received = Received::default();
+        loop {
+            let read: isize = unsafe {
+                libc::read(
+                    self.read_fd,
+                    buffer.as_mut_ptr() as *mut c_void,
+                    buffer.len(),
+                )
+            };
+            if read <= 0 {
+                break;
+            }
+            for &signal in buffer[..read as usize].iter() {
+                match c_int::from(signal) {
+                    libc::SIGHUP => received.hangup = true,
+                    libc::SIGCHLD => received.child_exited = true,
+                    _ => {}
+                }
+            }
+        }
+        received
+    }
+}
+
+/// Reaps every child process which has exited, returning their pids.
+///
+/// Programs are spawned and forgotten about, so without this they would be
+/// left as zombies.
+pub fn reap_children() -> Vec<u32> {
+    let mut reaped: Vec<u32> = Vec::new();
+    loop {
+        let pid: libc::pid_t = unsafe { libc::waitpid(-1, std::ptr::null_mut(), libc::WNOHANG) };
+        if pid <= 0 {
+            break;
+        }
+        reaped.push(pid as u32);
+    }
+    reaped
+}
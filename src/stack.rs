@@ -129,6 +129,59 @@ impl<T> Stack<T> {
         }
     }
 
+    /// Swaps the positions of the first elements matching each predicate.
+    ///
+    /// The focus moves with the focused element.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no element matches either predicate.
+    pub fn swap<P, Q>(&mut self, p: P, q: Q)
+    where
+        P: FnMut(&T) -> bool,
+        Q: FnMut(&T) -> bool,
+    {
+        let i = self
+            .iter()
+            .position(p)
+            .expect("No element in stack matches predicate");
+        let j = self
+            .iter()
+            .position(q)
+            .expect("No element in stack matches predicate");
+        if i == j {
+            return;
+        }
+
+        let focus = match self.before.len() {
+            focus if focus == i => j,
+            focus if focus == j => i,
+            focus => focus,
+        };
+        let mut elements: VecDeque<T> = self.before.drain(..).chain(self.after.drain(..)).collect();
+        elements.swap(i, j);
+        self.after = elements.split_off(focus);
+        self.before = elements;
+    }
+
+    /// Returns a copy of the stack with only the elements matching the
+    /// predicate, in the same order.
+    ///
+    /// The focused element stays focused if it matches, otherwise focus moves
+    /// to the next matching element (or the last one, if none follow it).
+    pub fn filtered<P>(&self, mut p: P) -> Stack<T>
+    where
+        P: FnMut(&T) -> bool,
+        T: Clone,
+    {
+        let mut filtered = Stack {
+            before: self.before.iter().filter(|e| p(e)).cloned().collect(),
+            after: self.after.iter().filter(|e| p(e)).cloned().collect(),
+        };
+        filtered.ensure_after_not_empty();
+        filtered
+    }
+
     /// Shifts focus to the next element.
     pub fn focus_next(&mut self) {
         if self.len() < 2 {
@@ -300,6 +353,40 @@ mod test {
         assert_eq!(stack, vec![2, 3]);
     }
 
+    #[test]
+    fn test_swap() {
+        let mut stack = Stack::from(vec![1, 2, 3, 4]);
+        stack.focus(|v| v == &2);
+        stack.swap(|v| v == &2, |v| v == &4);
+        assert_eq!(stack, vec![1, 4, 3, 2]);
+        assert_eq!(stack.focused(), Some(&2));
+
+        stack.swap(|v| v == &1, |v| v == &3);
+        assert_eq!(stack, vec![3, 4, 1, 2]);
+        assert_eq!(stack.focused(), Some(&2));
+
+        stack.swap(|v| v == &4, |v| v == &4);
+        assert_eq!(stack, vec![3, 4, 1, 2]);
+    }
+
+    #[test]
+    fn test_filtered() {
+        let mut stack = Stack::from(vec![1, 2, 3, 4]);
+        stack.focus(|v| v == &2);
+        let evens = stack.filtered(|v| v % 2 == 0);
+        assert_eq!(evens, vec![2, 4]);
+        assert_eq!(evens.focused(), Some(&2));
+
+        let odds = stack.filtered(|v| v % 2 == 1);
+        assert_eq!(odds, vec![1, 3]);
+        assert_eq!(odds.focused(), Some(&3));
+
+        stack.focus(|v| v == &4);
+        let small = stack.filtered(|v| v < &3);
+        assert_eq!(small, vec![1, 2]);
+        assert_eq!(small.focused(), Some(&2));
+    }
+
     #[test]
     fn test_focus_next() {
         let vec = vec![1, 2, 3];
@@ -0,0 +1,192 @@
+//! A serializable snapshot of the window manager's state.
+//!
+//! This is what the `get_tree` IPC query returns, so that bars and scripts
+//! can inspect workspaces and windows without scraping the log.
+
+use serde::Serialize;
+
+use crate::monitor::Monitor;
+use crate::workspaces::WorkSpace;
+use crate::x::{Connection, WindowGeometry, WindowId};
+use crate::{Viewport, Volan};
+
+#[derive(Debug, Serialize)]
+pub struct Tree {
+    /// The active keymap mode, if any.
+    pub mode: Option<String>,
+    pub monitors: Vec<MonitorNode>,
+    pub workspaces: Vec<WorkSpaceNode>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MonitorNode {
+    pub name: String,
+    pub focused: bool,
+    /// The whole output.
+    pub geometry: Viewport,
+    /// The part of the output not reserved by docks.
+    pub viewport: Viewport,
+    pub workspace: Option<String>,
+    pub docks: Vec<DockNode>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DockNode {
+    pub id: WindowId,
+    pub strut: Option<Strut>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Strut {
+    pub left: u32,
+    pub right: u32,
+    pub top: u32,
+    pub bottom: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WorkSpaceNode {
+    pub name: String,
+    pub active: bool,
+    pub focused: bool,
+    pub layout: Option<String>,
+    pub master: Option<WindowId>,
+    pub windows: Vec<WindowNode>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WindowNode {
+    pub id: WindowId,
+    pub focused: bool,
+    pub floating: bool,
+    pub fullscreen: bool,
+    pub title: Option<String>,
+    pub instance: Option<String>,
+    pub class: Option<String>,
+    pub geometry: Option<Geometry>,
+}
+
+/// A window's position and size. Unlike in `WindowGeometry`, the position is
+/// signed, as windows can be partly off the left or top of the screen.
+#[derive(Debug, Serialize)]
+pub struct Geometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl From<WindowGeometry> for Geometry {
+    fn from(geometry: WindowGeometry) -> Geometry {
+        Geometry {
+            x: geometry.x as i32,
+            y: geometry.y as i32,
+            width: geometry.width,
+            height: geometry.height,
+        }
+    }
+}
+
+impl Tree {
+    pub fn new(wm: &Volan) -> Tree {
+        let focused_name: &str = wm.group().name();
+        Tree {
+            mode: wm.keys.mode().map(String::from),
+            monitors: (0..wm.monitors.len())
+                .map(|index| MonitorNode::new(wm, index))
+                .collect(),
+            workspaces: wm
+                .workspaces
+                .iter()
+                .map(|group| {
+                    WorkSpaceNode::new(&wm.connection, group, group.name() == focused_name)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl MonitorNode {
+    fn new(wm: &Volan, index: usize) -> MonitorNode {
+        let monitor: &Monitor = &wm.monitors[index];
+        let (viewport, geometry) = wm.monitor_areas(index);
+        MonitorNode {
+            name: monitor.name().to_string(),
+            focused: index == wm.focused_monitor(),
+            geometry,
+            viewport,
+            workspace: monitor.workspace().map(String::from),
+            docks: monitor
+                .screen()
+                .docks()
+                .into_iter()
+                .map(|(id, strut)| DockNode {
+                    id,
+                    strut: strut.map(|(left, right, top, bottom)| Strut {
+                        left,
+                        right,
+                        top,
+                        bottom,
+                    }),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl WorkSpaceNode {
+    fn new(connection: &Connection, group: &WorkSpace, focused: bool) -> WorkSpaceNode {
+        WorkSpaceNode {
+            name: group.name().to_string(),
+            active: group.is_active(),
+            focused,
+            layout: group.layout_name().map(String::from),
+            master: group.master().cloned(),
+            windows: group
+                .windows()
+                .map(|id| WindowNode::new(connection, group, id))
+                .collect(),
+        }
+    }
+}
+
+impl WindowNode {
+    fn new(connection: &Connection, group: &WorkSpace, id: &WindowId) -> WindowNode {
+        let (instance, class) = match connection.get_window_class(id) {
+            Some((instance, class)) => (Some(instance), Some(class)),
+            None => (None, None),
+        };
+        WindowNode {
+            id: *id,
+            focused: group.focused_window() == Some(id),
+            floating: group.is_floating(id),
+            fullscreen: group.is_fullscreen(id),
+            title: connection.get_window_title(id),
+            instance,
+            class,
+            geometry: connection.get_window_rect(id).map(Geometry::from),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Geometry;
+    use crate::x::WindowGeometry;
+
+    #[test]
+    fn test_negative_position() {
+        // As get_window_rect() gives it for a window at (-10, -20).
+        let geometry = Geometry::from(WindowGeometry {
+            x: -10i16 as u32,
+            y: -20i16 as u32,
+            width: 100,
+            height: 50,
+        });
+        assert_eq!((geometry.x, geometry.y), (-10, -20));
+        assert_eq!(
+            serde_json::to_string(&geometry).unwrap(),
+            r#"{"x":-10,"y":-20,"width":100,"height":50}"#
+        );
+    }
+}
@@ -1,9 +1,13 @@
+use std::cmp;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 
 use super::Viewport;
+use crate::events::{EventQueue, WmEvent};
 use crate::layout::Layout;
+use crate::settings::Borders;
 use crate::stack::Stack;
-use crate::x::{Connection, WindowId};
+use crate::x::{Connection, WindowGeometry, WindowId, WindowState};
 
 #[derive(Clone)]
 pub struct WorkSpaceBuilder {
@@ -23,30 +27,77 @@ impl WorkSpaceBuilder {
         }
     }
 
-    pub fn build(self, connection: Rc<Connection>, layouts: Vec<Box<dyn Layout>>) -> WorkSpace {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn build(
+        self,
+        connection: Rc<Connection>,
+        events: EventQueue,
+        mut layouts: Vec<Box<dyn Layout>>,
+        borders: Borders,
+    ) -> WorkSpace {
+        for layout in layouts.iter_mut() {
+            layout.set_border_width(borders.width);
+        }
         let mut layouts_stack: Stack<Box<dyn super::layout::Layout>> = Stack::from(layouts);
         layouts_stack.focus(|layout| layout.name() == self.default_layout);
 
         WorkSpace {
             connection,
+            events,
             name: self.name.clone(),
             active: false,
+            has_focus: false,
             stack: Stack::new(),
             layouts: layouts_stack,
             viewport: Viewport::default(),
+            screen: Viewport::default(),
             master: None,
+            floating: HashSet::new(),
+            float_geometry: HashMap::new(),
+            fullscreen: HashSet::new(),
+            urgent: HashSet::new(),
+            borders,
         }
     }
 }
 
+/// A window taken out of a workspace, with how it was shown there so that
+/// another workspace can show it the same way.
+pub struct RemovedWindow {
+    pub window_id: WindowId,
+    /// Where it was floating, if it was.
+    pub floating: Option<WindowGeometry>,
+    pub fullscreen: bool,
+    pub urgent: bool,
+}
+
 pub struct WorkSpace {
     name: String,
     connection: Rc<Connection>,
+    events: EventQueue,
     active: bool,
+    /// Whether this workspace holds the input focus. Several workspaces can
+    /// be active at once, one per monitor, but only one of them is focused.
+    has_focus: bool,
     stack: Stack<WindowId>,
     layouts: Stack<Box<dyn Layout>>,
     viewport: Viewport,
+    /// The whole screen, including the space reserved by docks.
+    screen: Viewport,
     master: Option<WindowId>,
+    /// Windows which are skipped by the layout and kept above tiled windows.
+    floating: HashSet<WindowId>,
+    /// The last geometry of each window that has been floating, so that it
+    /// returns to the same place when floated again.
+    float_geometry: HashMap<WindowId, WindowGeometry>,
+    /// Windows which cover the whole screen, above everything else.
+    fullscreen: HashSet<WindowId>,
+    /// Windows asking for attention, through WM_HINTS or _NET_WM_STATE.
+    urgent: HashSet<WindowId>,
+    borders: Borders,
 }
 
 impl WorkSpace {
@@ -54,17 +105,75 @@ impl WorkSpace {
         &self.name
     }
 
-    pub fn activate(&mut self, viewport: Viewport) {
+    pub fn rename<S: Into<String>>(&mut self, name: S) {
+        self.name = name.into();
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Returns the name of the layout currently in use.
+    pub fn layout_name(&self) -> Option<&str> {
+        self.layouts.focused().map(|layout| layout.name())
+    }
+
+    pub fn master(&self) -> Option<&WindowId> {
+        self.master.as_ref()
+    }
+
+    /// Returns the workspace's windows in stack order.
+    pub fn windows(&self) -> impl Iterator<Item = &WindowId> {
+        self.stack.iter()
+    }
+
+    /// Returns the workspace's windows from the bottom of the X stacking order
+    /// to the top: tiled windows, then floating and finally fullscreen
+    /// windows, with the focused one at the top of its kind.
+    pub fn stacking_order(&self) -> Vec<WindowId> {
+        let focused: Option<&WindowId> = self.stack.focused();
+        let kinds: [&dyn Fn(&WindowId) -> bool; 3] =
+            [&|w| self.is_tiled(w), &|w| self.is_floating(w), &|w| {
+                self.is_fullscreen(w)
+            }];
+        let mut order: Vec<WindowId> = Vec::new();
+        for is_kind in kinds.iter() {
+            order.extend(
+                self.stack
+                    .iter()
+                    .filter(|w| is_kind(w) && Some(*w) != focused)
+                    .chain(focused.filter(|w| is_kind(w)))
+                    .cloned(),
+            );
+        }
+        order
+    }
+
+    pub fn focused_window(&self) -> Option<&WindowId> {
+        self.stack.focused()
+    }
+
+    pub fn activate(&mut self, viewport: Viewport, screen: Viewport) {
         info!("Activating workspace: {}", self.name());
         self.active = true;
         self.viewport = viewport;
+        self.screen = screen;
         self.perform_layout();
     }
 
+    /// Gives the input focus to this workspace, or takes it away.
+    pub fn set_has_focus(&mut self, has_focus: bool) {
+        self.has_focus = has_focus;
+        if has_focus && self.active {
+            self.focus_x_window();
+        }
+    }
+
     pub fn decrease_innergaps(&mut self) {
         if let Some(layout) = self.layouts.focused_mut() {
             layout.decrease_innergaps();
         }
+        self.gaps_changed();
         self.perform_layout();
     }
 
@@ -72,6 +181,7 @@ impl WorkSpace {
         if let Some(layout) = self.layouts.focused_mut() {
             layout.increase_innergaps();
         }
+        self.gaps_changed();
         self.perform_layout();
     }
 
@@ -79,6 +189,7 @@ impl WorkSpace {
         if let Some(layout) = self.layouts.focused_mut() {
             layout.decrease_outergaps();
         }
+        self.gaps_changed();
         self.perform_layout();
     }
 
@@ -86,6 +197,16 @@ impl WorkSpace {
         if let Some(layout) = self.layouts.focused_mut() {
             layout.increase_outergaps();
         }
+        self.gaps_changed();
+        self.perform_layout();
+    }
+
+    /// Sets the gaps of every layout, not just the one in use.
+    pub fn set_gaps(&mut self, innergaps: u32, outergaps: u32) {
+        for layout in self.layouts.iter_mut() {
+            layout.set_gaps(innergaps, outergaps);
+        }
+        self.gaps_changed();
         self.perform_layout();
     }
 
@@ -103,8 +224,33 @@ impl WorkSpace {
         self.perform_layout();
     }
 
-    pub fn update_viewport(&mut self, viewport: Viewport) {
+    /// Grows (or, for negative amounts, shrinks) the master area.
+    pub fn resize_master(&mut self, amount: i16) {
+        if let Some(layout) = self.layouts.focused_mut() {
+            if amount >= 0 {
+                layout.increase_master(&self.viewport, amount);
+            } else {
+                layout.decrease_master(&self.viewport, -amount);
+            }
+        }
+        self.perform_layout();
+    }
+
+    /// Swaps the places of two windows, including which is the master.
+    pub fn swap_windows(&mut self, a: &WindowId, b: &WindowId) {
+        info!("Swapping windows in workspace {}: {} {}", self.name(), a, b);
+        self.stack.swap(|w| w == a, |w| w == b);
+        if self.master.as_ref() == Some(a) {
+            self.master = Some(*b);
+        } else if self.master.as_ref() == Some(b) {
+            self.master = Some(*a);
+        }
+        self.perform_layout();
+    }
+
+    pub fn update_viewport(&mut self, viewport: Viewport, screen: Viewport) {
         self.viewport = viewport;
+        self.screen = screen;
         self.perform_layout();
     }
 
@@ -118,6 +264,30 @@ impl WorkSpace {
         self.active = false;
     }
 
+    fn focus_changed(&self) {
+        self.events.push(WmEvent::FocusChanged {
+            workspace: self.name.clone(),
+            window: self.stack.focused().cloned(),
+        });
+    }
+
+    fn layout_changed(&self) {
+        self.events.push(WmEvent::LayoutChanged {
+            workspace: self.name.clone(),
+            layout: self.layout_name().map(String::from),
+        });
+    }
+
+    fn gaps_changed(&self) {
+        if let Some((inner, outer)) = self.layouts.focused().map(|layout| layout.gaps()) {
+            self.events.push(WmEvent::GapsChanged {
+                workspace: self.name.clone(),
+                inner,
+                outer,
+            });
+        }
+    }
+
     fn change_master(&mut self) {
         if !self.stack.is_empty() {
             self.master = Some(*self.stack.focused().unwrap());
@@ -129,51 +299,274 @@ impl WorkSpace {
             return;
         }
 
+        self.update_borders();
+        let tiled: Stack<WindowId> = self.stack.filtered(|w| self.is_tiled(w));
+        let master: Option<WindowId> = self.master.filter(|w| self.is_tiled(w));
         if let Some(layout) = self.layouts.focused() {
-            layout.layout(&self.connection, &self.viewport, &self.stack, &self.master)
+            layout.layout(&self.connection, &self.viewport, &tiled, &master)
         }
+        self.layout_floating();
+        self.layout_fullscreen();
 
-        // Tell X to focus the focused window for this workspace, or to unset
-        // it's focus if we have no windows.
+        if self.has_focus {
+            self.focus_x_window();
+        }
+
+        // Windows moving under the pointer shouldn't steal the focus.
+        self.connection.discard_enter_events();
+    }
+
+    /// Colours each window's border to show whether it is focused, asking
+    /// for attention or floating. Fullscreen windows have no border.
+    fn update_borders(&self) {
+        let focused: Option<&WindowId> = self.stack.focused();
+        for window_id in self.stack.iter() {
+            let width: u32 = if self.is_fullscreen(window_id) {
+                0
+            } else {
+                self.borders.width
+            };
+            let colour: u32 = if Some(window_id) == focused {
+                self.borders.focused
+            } else if self.urgent.contains(window_id) {
+                self.borders.urgent
+            } else if self.is_floating(window_id) {
+                self.borders.floating
+            } else {
+                self.borders.unfocused
+            };
+            self.connection.set_window_border(window_id, width, colour);
+        }
+    }
+
+    /// Tell X to focus the focused window for this workspace, or to unset
+    /// it's focus if we have no windows.
+    fn focus_x_window(&self) {
         match self.stack.focused() {
             Some(window_id) => self.connection.focus_window(window_id),
             None => self.connection.focus_nothing(),
         }
     }
 
+    /// Places the floating windows at their remembered geometry, above the
+    /// tiled windows. The focused window is raised above the others.
+    fn layout_floating(&self) {
+        let focused: Option<&WindowId> = self.stack.focused();
+        let floating = self
+            .stack
+            .iter()
+            .filter(|w| self.is_floating(w) && Some(*w) != focused)
+            .chain(focused.filter(|w| self.is_floating(w)));
+        for window_id in floating {
+            if let Some(geometry) = self.float_geometry.get(window_id) {
+                self.connection.disable_window_tracking(window_id);
+                self.connection.configure_window(window_id, geometry);
+                self.connection.enable_window_tracking(window_id);
+            }
+            self.connection.stack_window_above(window_id);
+        }
+    }
+
+    /// Gives fullscreen windows the whole screen, ignoring docks and gaps.
+    fn layout_fullscreen(&self) {
+        let geometry: WindowGeometry = WindowGeometry::default(&self.screen);
+        let focused: Option<&WindowId> = self.stack.focused();
+        let fullscreen = self
+            .stack
+            .iter()
+            .filter(|w| self.is_fullscreen(w) && Some(*w) != focused)
+            .chain(focused.filter(|w| self.is_fullscreen(w)));
+        for window_id in fullscreen {
+            self.connection.disable_window_tracking(window_id);
+            self.connection.configure_window(window_id, &geometry);
+            self.connection.enable_window_tracking(window_id);
+            self.connection.stack_window_above(window_id);
+        }
+    }
+
+    /// Returns whether the window is floating. Fullscreen windows are neither
+    /// floating nor tiled while they are fullscreen.
+    pub fn is_floating(&self, window_id: &WindowId) -> bool {
+        self.floating.contains(window_id) && !self.is_fullscreen(window_id)
+    }
+
+    pub fn is_fullscreen(&self, window_id: &WindowId) -> bool {
+        self.fullscreen.contains(window_id)
+    }
+
+    fn is_tiled(&self, window_id: &WindowId) -> bool {
+        !self.floating.contains(window_id) && !self.is_fullscreen(window_id)
+    }
+
+    /// Makes a window fullscreen or returns it to its previous place, updating
+    /// its _NET_WM_STATE to match.
+    pub fn set_fullscreen(&mut self, window_id: &WindowId, fullscreen: bool) {
+        info!(
+            "Setting fullscreen to {} for window in workspace {}: {}",
+            fullscreen,
+            self.name(),
+            window_id
+        );
+        if fullscreen {
+            self.fullscreen.insert(*window_id);
+        } else {
+            self.fullscreen.remove(window_id);
+        }
+        self.connection
+            .set_window_state(window_id, WindowState::Fullscreen, fullscreen);
+        self.perform_layout();
+    }
+
+    /// Records whether a window is asking for attention, colouring its border
+    /// to match.
+    pub fn set_urgent(&mut self, window_id: &WindowId, urgent: bool) {
+        let changed: bool = if urgent {
+            self.urgent.insert(*window_id)
+        } else {
+            self.urgent.remove(window_id)
+        };
+        if changed && self.active {
+            self.update_borders();
+        }
+    }
+
+    pub fn toggle_fullscreen_focused(&mut self) {
+        if let Some(window_id) = self.stack.focused().cloned() {
+            let fullscreen: bool = !self.is_fullscreen(&window_id);
+            self.set_fullscreen(&window_id, fullscreen);
+        }
+    }
+
+    /// Floats the focused window if it is tiled, or tiles it if it is
+    /// floating.
+    pub fn toggle_floating_focused(&mut self) {
+        let window_id: WindowId = match self.stack.focused() {
+            Some(window_id) => *window_id,
+            None => return,
+        };
+        if self.floating.remove(&window_id) {
+            info!("Tiling window in workspace {}: {}", self.name(), window_id);
+        } else {
+            info!(
+                "Floating window in workspace {}: {}",
+                self.name(),
+                window_id
+            );
+            if !self.float_geometry.contains_key(&window_id) {
+                let geometry: WindowGeometry = self
+                    .connection
+                    .get_window_rect(&window_id)
+                    .unwrap_or_else(|| WindowGeometry::default(&self.viewport));
+                self.float_geometry.insert(window_id, geometry);
+            }
+            self.floating.insert(window_id);
+        }
+        self.perform_layout();
+    }
+
+    /// Moves a floating window by the given offset.
+    pub fn move_floating(&mut self, window_id: &WindowId, dx: i16, dy: i16) {
+        if let Some(geometry) = self.float_geometry.get_mut(window_id) {
+            // X positions are signed, so let them wrap: windows can be dragged
+            // partly off the left and top of the screen.
+            geometry.x = (geometry.x as i32 + i32::from(dx)) as u32;
+            geometry.y = (geometry.y as i32 + i32::from(dy)) as u32;
+            self.connection.configure_window(window_id, geometry);
+        }
+    }
+
+    /// Resizes a floating window by the given amounts, keeping its top left
+    /// corner in place.
+    pub fn resize_floating(&mut self, window_id: &WindowId, dw: i16, dh: i16) {
+        if let Some(geometry) = self.float_geometry.get_mut(window_id) {
+            geometry.width = cmp::max(1, geometry.width as i32 + i32::from(dw)) as u32;
+            geometry.height = cmp::max(1, geometry.height as i32 + i32::from(dh)) as u32;
+            self.connection.configure_window(window_id, geometry);
+        }
+    }
+
+    /// Forgets about a window which is leaving the workspace.
+    fn forget_window_state(&mut self, window_id: WindowId) -> RemovedWindow {
+        let floating: bool = self.floating.remove(&window_id);
+        let geometry: Option<WindowGeometry> = self.float_geometry.remove(&window_id);
+        RemovedWindow {
+            window_id,
+            floating: geometry.filter(|_| floating),
+            fullscreen: self.fullscreen.remove(&window_id),
+            urgent: self.urgent.remove(&window_id),
+        }
+    }
+
+    /// Adds a window taken out of another workspace, floating or fullscreen
+    /// as it was there.
+    pub fn add_removed_window(&mut self, removed: RemovedWindow) {
+        match removed.floating {
+            Some(geometry) => self.add_floating_window(removed.window_id, geometry),
+            None => self.add_window(removed.window_id),
+        }
+        if removed.fullscreen {
+            self.set_fullscreen(&removed.window_id, true);
+        }
+        if removed.urgent {
+            self.set_urgent(&removed.window_id, true);
+        }
+    }
+
     pub fn add_window(&mut self, window_id: WindowId) {
         info!("Adding window to workspace {}: {}", self.name(), window_id);
         self.stack.push(window_id);
         self.master = Some(window_id);
+        self.focus_changed();
+        self.perform_layout();
+    }
+
+    /// Adds a window which floats at the given geometry rather than being
+    /// tiled. The master window is left as it is.
+    pub fn add_floating_window(&mut self, window_id: WindowId, geometry: WindowGeometry) {
+        info!(
+            "Adding floating window to workspace {}: {}",
+            self.name(),
+            window_id
+        );
+        self.stack.push(window_id);
+        self.floating.insert(window_id);
+        self.float_geometry.insert(window_id, geometry);
+        self.focus_changed();
         self.perform_layout();
     }
 
-    pub fn remove_window(&mut self, window_id: &WindowId) -> WindowId {
+    pub fn remove_window(&mut self, window_id: &WindowId) -> RemovedWindow {
         info!(
             "Removing window from workspace {}: {}",
             self.name(),
             window_id
         );
         let removed: WindowId = self.stack.remove(|w| w == window_id);
+        let removed: RemovedWindow = self.forget_window_state(removed);
         self.change_master();
+        self.focus_changed();
         self.perform_layout();
         removed
     }
 
-    pub fn remove_focused(&mut self) -> Option<WindowId> {
+    pub fn remove_focused(&mut self) -> Option<RemovedWindow> {
         info!(
             "Removing focused window from workspace {}: {:?}",
             self.name(),
             self.stack.focused()
         );
-        let removed: Option<WindowId> = self.stack.remove_focused();
+        let removed: Option<RemovedWindow> = self
+            .stack
+            .remove_focused()
+            .map(|window_id| self.forget_window_state(window_id));
         self.change_master();
+        self.focus_changed();
         self.perform_layout();
-        removed.map(|window| {
-            self.connection.disable_window_tracking(&window);
-            self.connection.unmap_window(&window);
-            self.connection.enable_window_tracking(&window);
-            window
+        removed.inspect(|removed| {
+            let window: &WindowId = &removed.window_id;
+            self.connection.disable_window_tracking(window);
+            self.connection.unmap_window(window);
+            self.connection.enable_window_tracking(window);
         })
     }
 
@@ -188,6 +581,7 @@ impl WorkSpace {
             window_id
         );
         self.stack.focus(|id| id == window_id);
+        self.focus_changed();
         self.perform_layout();
     }
 
@@ -206,12 +600,14 @@ impl WorkSpace {
             self.stack.focused()
         );
         self.change_master();
+        self.focus_changed();
         self.perform_layout();
     }
 
     pub fn focus_previous(&mut self) {
         self.stack.focus_previous();
         self.change_master();
+        self.focus_changed();
         info!(
             "Focusing previous window in workspace {}: {:?}",
             self.name(),
@@ -227,6 +623,7 @@ impl WorkSpace {
             self.name(),
             self.layouts.focused()
         );
+        self.layout_changed();
         self.perform_layout();
     }
 
@@ -238,6 +635,7 @@ impl WorkSpace {
             self.layouts.focused()
         );
         self.layouts.focus_previous();
+        self.layout_changed();
         self.perform_layout();
     }
 }
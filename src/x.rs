@@ -1,11 +1,20 @@
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::cmp;
+use std::collections::{HashMap, VecDeque};
 use std::fmt;
+use std::os::unix::io::{AsRawFd, RawFd};
 
 use failure::{format_err, ResultExt};
+use serde::Serialize;
+use x11::keysym;
+use xcb::randr;
 use xcb_util::keysyms::KeySymbols;
 use xcb_util::{ewmh, icccm};
 
-use crate::keys::{KeyCombo, KeyHandlers};
+use crate::ipc;
+use crate::keys::{KeyCombo, KeyHandlers, ModKey, ModMask};
+use crate::mouse::{MouseCombo, MouseHandlers};
+use crate::signals::{Received, Signals};
 use crate::stack::Stack;
 use crate::workspaces::WorkSpace;
 use crate::Result;
@@ -13,8 +22,11 @@ use crate::Viewport;
 
 pub use self::ewmh::StrutPartial;
 
+/// The UrgencyHint flag of WM_HINTS, from the ICCCM.
+const URGENCY_HINT: u32 = 1 << 8;
+
 /// A handle to an X Window.
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq, Serialize)]
 pub struct WindowId(xcb::Window);
 
 impl WindowId {
@@ -29,6 +41,7 @@ impl fmt::Display for WindowId {
     }
 }
 
+#[derive(Copy, Clone, Debug, Serialize)]
 pub struct WindowGeometry {
     pub x: u32,
     pub y: u32,
@@ -102,6 +115,18 @@ pub struct Connection {
     atoms: InternedAtoms,
     window_type_lookup: HashMap<xcb::Atom, WindowType>,
     window_state_lookup: HashMap<xcb::Atom, WindowState>,
+    /// The mods set by CapsLock, NumLock and ScrollLock, which are ignored
+    /// when matching bindings.
+    lock_mask: Cell<ModMask>,
+    /// The keycodes of the keysyms looked up for key grabs, each with the mods
+    /// needed to type the keysym on it, kept until the keyboard mapping
+    /// changes so it isn't fetched again for every window.
+    keycodes: RefCell<HashMap<xcb::Keysym, Vec<(xcb::Keycode, ModMask)>>>,
+    /// The first event number of the RandR extension, if the server has it.
+    randr_event_base: Option<u8>,
+    /// Events read from the X connection while discarding enter events, to be
+    /// yielded by the `EventLoop` before any others.
+    deferred_events: RefCell<VecDeque<xcb::GenericEvent>>,
 }
 
 impl Connection {
@@ -155,14 +180,121 @@ impl Connection {
             WindowState::DemandsAttention,
         );
 
-        Ok(Connection {
+        let randr_event_base: Option<u8> = conn
+            .get_extension_data(randr::id())
+            .filter(|data| data.present())
+            .map(|data| data.first_event());
+
+        let connection = Connection {
             conn,
             root: WindowId(root),
             screen_idx,
             atoms,
             window_type_lookup: types,
             window_state_lookup: state,
-        })
+            lock_mask: Cell::new(xcb::MOD_MASK_LOCK),
+            keycodes: RefCell::new(HashMap::new()),
+            randr_event_base,
+            deferred_events: RefCell::new(VecDeque::new()),
+        };
+        connection.update_lock_mask();
+        Ok(connection)
+    }
+
+    /// Forgets the keycodes looked up so far and works out the lock mods
+    /// again, for when the keyboard or modifier mapping has changed.
+    pub fn refresh_keyboard_mapping(&self) {
+        self.keycodes.borrow_mut().clear();
+        self.update_lock_mask();
+    }
+
+    /// Returns the keycodes of each keysym, only fetching the keyboard mapping
+    /// if one of them hasn't been looked up since it last changed.
+    ///
+    /// Each keycode comes with Shift if the keysym is only typed on it with
+    /// Shift held, such as `exclam` on a US keyboard. Keycodes which type the
+    /// keysym without any mods come first.
+    fn keycodes(&self, keysyms: &[xcb::Keysym]) -> Vec<Vec<(xcb::Keycode, ModMask)>> {
+        let mut keycodes = self.keycodes.borrow_mut();
+        if keysyms.iter().any(|keysym| !keycodes.contains_key(keysym)) {
+            let key_symbols = KeySymbols::new(&self.conn);
+            for keysym in keysyms.iter() {
+                keycodes.entry(*keysym).or_insert_with(|| {
+                    let mut found: Vec<(xcb::Keycode, ModMask)> = key_symbols
+                        .get_keycode(*keysym)
+                        .map(|keycode| {
+                            let shifted: bool = key_symbols.get_keysym(keycode, 0) != *keysym
+                                && key_symbols.get_keysym(keycode, 1) == *keysym;
+                            (keycode, if shifted { xcb::MOD_MASK_SHIFT } else { 0 })
+                        })
+                        .collect();
+                    found.sort_by_key(|&(_, mods)| mods);
+                    found
+                });
+            }
+        }
+        keysyms
+            .iter()
+            .map(|keysym| keycodes[keysym].clone())
+            .collect()
+    }
+
+    /// Works out which mods are set by the lock keys. CapsLock is always
+    /// Lock, but NumLock and ScrollLock set whichever mods their keys are
+    /// mapped to, usually Mod2 and Mod5.
+    fn update_lock_mask(&self) {
+        let lock_keycodes: Vec<xcb::Keycode> = self
+            .keycodes(&[keysym::XK_Num_Lock, keysym::XK_Scroll_Lock])
+            .concat()
+            .into_iter()
+            .map(|(keycode, _)| keycode)
+            .collect();
+
+        let mut lock_mask: ModMask = xcb::MOD_MASK_LOCK;
+        match xcb::get_modifier_mapping(&self.conn).get_reply() {
+            Ok(reply) if reply.keycodes_per_modifier() > 0 => {
+                // The keycodes are listed in the same order as the mask bits:
+                // Shift, Lock, Control, then Mod1 to Mod5.
+                let per_modifier: usize = reply.keycodes_per_modifier() as usize;
+                for (index, keycodes) in reply.keycodes().chunks(per_modifier).enumerate() {
+                    if keycodes
+                        .iter()
+                        .any(|k| *k != 0 && lock_keycodes.contains(k))
+                    {
+                        lock_mask |= 1 << index;
+                    }
+                }
+            }
+            Ok(_) => {}
+            Err(_) => error!("Could not get the modifier mapping to find NumLock"),
+        }
+        debug!("Ignoring lock mods {:#x}", lock_mask);
+        self.lock_mask.set(lock_mask);
+    }
+
+    /// Returns every combination of the lock mods, including none of them.
+    /// Bindings are grabbed with each, so they work whatever locks are on.
+    fn lock_combinations(&self) -> Vec<ModMask> {
+        let lock_mask: ModMask = self.lock_mask.get();
+        let locks: Vec<ModMask> = (0..8)
+            .map(|bit| 1 << bit)
+            .filter(|mask| lock_mask & mask != 0)
+            .collect();
+        (0..1u32 << locks.len())
+            .map(|subset| {
+                locks
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, _)| subset & (1 << index) != 0)
+                    .fold(0, |mask, (_, lock)| mask | lock)
+            })
+            .collect()
+    }
+
+    /// Returns the mods from an event's state which bindings are matched on,
+    /// without pointer buttons or lock mods.
+    fn binding_mods(&self, state: u16) -> ModMask {
+        u32::from(state) & ModKey::mask_all() & !self.lock_mask.get()
     }
 
     /// Returns the Atom identifier associated with the atom_name str.
@@ -188,25 +320,92 @@ impl Connection {
             .context("Could not register SUBSTRUCTURE_NOTIFY/REDIRECT")?;
 
         self.enable_window_key_events(&self.root, key_handlers);
+        self.init_ewmh();
+
+        if self.randr_event_base.is_some() {
+            randr::select_input(
+                &self.conn,
+                self.root.to_x(),
+                randr::NOTIFY_MASK_SCREEN_CHANGE as u16,
+            );
+        }
 
         Ok(())
     }
 
+    /// Announces that an EWMH compliant WM is running, and which parts of the
+    /// spec it supports.
+    fn init_ewmh(&self) {
+        // The check window must be a child of the root that points to itself,
+        // and carries the WM's name.
+        let check_window: xcb::Window = self.conn.generate_id();
+        xcb::create_window(
+            &self.conn,
+            xcb::COPY_FROM_PARENT as u8,
+            check_window,
+            self.root.to_x(),
+            -1,
+            -1,
+            1,
+            1,
+            0,
+            xcb::WINDOW_CLASS_INPUT_ONLY as u16,
+            xcb::COPY_FROM_PARENT,
+            &[],
+        );
+        ewmh::set_supporting_wm_check(&self.conn, self.root.to_x(), check_window);
+        ewmh::set_supporting_wm_check(&self.conn, check_window, check_window);
+        ewmh::set_wm_name(&self.conn, check_window, "volanwm");
+
+        let supported: Vec<xcb::Atom> = vec![
+            self.conn.SUPPORTED(),
+            self.conn.SUPPORTING_WM_CHECK(),
+            self.conn.CLIENT_LIST(),
+            self.conn.CLIENT_LIST_STACKING(),
+            self.conn.NUMBER_OF_DESKTOPS(),
+            self.conn.DESKTOP_NAMES(),
+            self.conn.CURRENT_DESKTOP(),
+            self.conn.ACTIVE_WINDOW(),
+            self.conn.WORKAREA(),
+            self.conn.CLOSE_WINDOW(),
+            self.conn.WM_NAME(),
+            self.conn.WM_DESKTOP(),
+            self.conn.WM_STATE(),
+            self.conn.WM_STATE_FULLSCREEN(),
+            self.conn.WM_STATE_DEMANDS_ATTENTION(),
+            self.conn.WM_STRUT_PARTIAL(),
+            self.conn.WM_WINDOW_TYPE(),
+            self.conn.WM_WINDOW_TYPE_DOCK(),
+            self.conn.WM_WINDOW_TYPE_DIALOG(),
+            self.conn.WM_WINDOW_TYPE_SPLASH(),
+            self.conn.WM_WINDOW_TYPE_UTILITY(),
+            self.conn.WM_WINDOW_TYPE_MENU(),
+            self.conn.WM_WINDOW_TYPE_TOOLTIP(),
+            self.conn.WM_WINDOW_TYPE_NOTIFICATION(),
+            self.conn.WM_WINDOW_TYPE_NORMAL(),
+        ];
+        ewmh::set_supported(&self.conn, self.screen_idx, &supported);
+    }
+
     /// Returns the ID of the root window.
     pub fn root_window_id(&self) -> &WindowId {
         &self.root
     }
 
-    pub fn update_ewmh_desktops(&self, workspace: &Stack<WorkSpace>) {
-        let group_names = workspace.iter().map(|g| g.name());
+    /// Publishes the EWMH properties describing the desktops and the windows
+    /// on them: desktop names and count, the current desktop, the client lists,
+    /// each window's desktop and the work area of each desktop, given in the
+    /// same order as the workspaces.
+    pub fn update_ewmh(&self, workspaces: &Stack<WorkSpace>, workareas: &[Viewport]) {
+        let group_names = workspaces.iter().map(|g| g.name());
         ewmh::set_desktop_names(&self.conn, self.screen_idx, group_names);
-        ewmh::set_number_of_desktops(&self.conn, self.screen_idx, workspace.len() as u32);
+        ewmh::set_number_of_desktops(&self.conn, self.screen_idx, workspaces.len() as u32);
 
         // Matching the current group on name isn't perfect, but it's good enough for
         // EWMH.
-        let focused_idx = workspace
+        let focused_idx = workspaces
             .focused()
-            .and_then(|focused| workspace.iter().position(|g| g.name() == focused.name()));
+            .and_then(|focused| workspaces.iter().position(|g| g.name() == focused.name()));
         match focused_idx {
             Some(idx) => {
                 ewmh::set_current_desktop(&self.conn, self.screen_idx, idx as u32);
@@ -215,6 +414,46 @@ impl Connection {
                 error!("Invariant: failed to get active group index");
             }
         };
+
+        let mut clients: Vec<xcb::Window> = Vec::new();
+        for (idx, group) in workspaces.iter().enumerate() {
+            for window_id in group.windows() {
+                ewmh::set_wm_desktop(&self.conn, window_id.to_x(), idx as u32);
+                clients.push(window_id.to_x());
+            }
+        }
+        ewmh::set_client_list(&self.conn, self.screen_idx, &clients);
+
+        // Stacking is bottom to top, and the windows of the active group are
+        // above the (unmapped) windows of the others.
+        let stacking: Vec<xcb::Window> = workspaces
+            .iter()
+            .filter(|g| Some(g.name()) != workspaces.focused().map(|f| f.name()))
+            .chain(workspaces.focused())
+            .flat_map(|g| g.stacking_order())
+            .map(|w| w.to_x())
+            .collect();
+        ewmh::set_client_list_stacking(&self.conn, self.screen_idx, &stacking);
+
+        let workarea: Vec<u32> = workareas
+            .iter()
+            .flat_map(|area| vec![area.x, area.y, area.width, area.height])
+            .collect();
+        xcb::change_property(
+            &self.conn,
+            xcb::PROP_MODE_REPLACE as u8,
+            self.root.to_x(),
+            self.conn.WORKAREA(),
+            xcb::ATOM_CARDINAL,
+            32,
+            &workarea,
+        );
+    }
+
+    /// Removes the EWMH properties the WM set on a window, as it is no longer
+    /// managed.
+    pub fn clear_ewmh_window(&self, window_id: &WindowId) {
+        xcb::delete_property(&self.conn, window_id.to_x(), self.conn.WM_DESKTOP());
     }
 
     pub fn top_level_windows(&self) -> Result<Vec<WindowId>> {
@@ -266,6 +505,56 @@ impl Connection {
             .unwrap_or_else(|_| Vec::new())
     }
 
+    /// Returns the window named by the ICCCM WM_TRANSIENT_FOR property, which
+    /// dialogs use to say which window they belong to.
+    pub fn get_transient_for(&self, window_id: &WindowId) -> Option<WindowId> {
+        xcb::get_property(
+            &self.conn,
+            false,
+            window_id.to_x(),
+            xcb::ATOM_WM_TRANSIENT_FOR,
+            xcb::ATOM_WINDOW,
+            0,
+            1,
+        )
+        .get_reply()
+        .ok()
+        .and_then(|reply| reply.value::<xcb::Window>().first().cloned())
+        .filter(|&parent| parent != xcb::NONE)
+        .map(WindowId)
+    }
+
+    /// Returns whether a window is asking for attention, through the
+    /// UrgencyHint in its WM_HINTS or _NET_WM_STATE_DEMANDS_ATTENTION.
+    pub fn is_urgent(&self, window_id: &WindowId) -> bool {
+        // The flags are read directly, as xcb-util's `WmHints::is_urgent()`
+        // never finds the hint.
+        let urgency_hint: bool = xcb::get_property(
+            &self.conn,
+            false,
+            window_id.to_x(),
+            xcb::ATOM_WM_HINTS,
+            xcb::ATOM_WM_HINTS,
+            0,
+            1,
+        )
+        .get_reply()
+        .ok()
+        .and_then(|reply| reply.value::<u32>().first().cloned())
+        .is_some_and(|flags| flags & URGENCY_HINT != 0);
+        urgency_hint
+            || self
+                .get_window_states(window_id)
+                .contains(&WindowState::DemandsAttention)
+    }
+
+    /// Returns the ID of the process which the window says it belongs to.
+    pub fn get_window_pid(&self, window_id: &WindowId) -> Option<u32> {
+        ewmh::get_wm_pid(&self.conn, window_id.to_x())
+            .get_reply()
+            .ok()
+    }
+
     pub fn get_strut_partial(&self, window_id: &WindowId) -> Option<StrutPartial> {
         ewmh::get_wm_strut_partial(&self.conn, window_id.to_x())
             .get_reply()
@@ -318,12 +607,41 @@ impl Connection {
         xcb::configure_window(&self.conn, window_id.to_x(), &values);
     }
 
+    /// Sets the width and colour of the window's border.
+    pub fn set_window_border(&self, window_id: &WindowId, width: u32, pixel: u32) {
+        let values = [(xcb::CONFIG_WINDOW_BORDER_WIDTH as u16, width)];
+        xcb::configure_window(&self.conn, window_id.to_x(), &values);
+        let values = [(xcb::CW_BORDER_PIXEL, pixel)];
+        xcb::change_window_attributes(&self.conn, window_id.to_x(), &values);
+    }
+
     pub fn stack_window_above(&self, window_id: &WindowId) {
         let values = [(xcb::CONFIG_WINDOW_STACK_MODE as u16, xcb::STACK_MODE_ABOVE)];
         xcb::configure_window(&self.conn, window_id.to_x(), &values);
     }
 
-    /// Get's the window's width and height.
+    /// Sets or clears a single EWMH state in the window's _NET_WM_STATE,
+    /// leaving its other states alone.
+    pub fn set_window_state(&self, window_id: &WindowId, state: WindowState, enabled: bool) {
+        let mut atoms: Vec<xcb::Atom> = ewmh::get_wm_state(&self.conn, window_id.to_x())
+            .get_reply()
+            .map(|reply| reply.atoms().to_vec())
+            .unwrap_or_else(|_| Vec::new());
+        let state_atom: Option<xcb::Atom> = self
+            .window_state_lookup
+            .iter()
+            .find(|(_, s)| **s == state)
+            .map(|(atom, _)| *atom);
+        if let Some(state_atom) = state_atom {
+            atoms.retain(|atom| *atom != state_atom);
+            if enabled {
+                atoms.push(state_atom);
+            }
+            ewmh::set_wm_state(&self.conn, window_id.to_x(), &atoms);
+        }
+    }
+
+    /// Gets the window's width and height.
     pub fn get_window_geometry(&self, window_id: &WindowId) -> (u32, u32) {
         let reply = xcb::get_geometry(&self.conn, window_id.to_x())
             .get_reply()
@@ -332,6 +650,108 @@ impl Connection {
         (u32::from(reply.width()), u32::from(reply.height()))
     }
 
+    /// Gets the window's position and size, relative to its parent.
+    ///
+    /// A position left of or above the parent wraps around, as with the other
+    /// geometries; cast it back to i32 to get the real position.
+    pub fn get_window_rect(&self, window_id: &WindowId) -> Option<WindowGeometry> {
+        xcb::get_geometry(&self.conn, window_id.to_x())
+            .get_reply()
+            .ok()
+            .map(|reply| WindowGeometry {
+                x: reply.x() as u32,
+                y: reply.y() as u32,
+                width: u32::from(reply.width()),
+                height: u32::from(reply.height()),
+            })
+    }
+
+    /// Returns the enabled RandR outputs. Outputs showing the same area, such
+    /// as mirrored displays, are only returned once.
+    ///
+    /// Falls back to a single output covering the root window if RandR is not
+    /// available or reports no outputs.
+    pub fn get_outputs(&self) -> Vec<Output> {
+        let mut outputs: Vec<Output> = Vec::new();
+        if self.randr_event_base.is_some() {
+            let resources =
+                randr::get_screen_resources_current(&self.conn, self.root.to_x()).get_reply();
+            match resources {
+                Ok(resources) => {
+                    let timestamp = resources.config_timestamp();
+                    for crtc in resources.crtcs() {
+                        if let Some(output) = self.get_crtc_output(*crtc, timestamp) {
+                            if !outputs.iter().any(|o| o.geometry == output.geometry) {
+                                outputs.push(output);
+                            }
+                        }
+                    }
+                }
+                Err(_) => error!("Could not get RandR screen resources"),
+            }
+        }
+        if outputs.is_empty() {
+            let (width, height): (u32, u32) = self.get_window_geometry(&self.root);
+            outputs.push(Output {
+                name: "default".to_string(),
+                geometry: Viewport {
+                    x: 0,
+                    y: 0,
+                    width,
+                    height,
+                },
+            });
+        }
+        debug!("Found outputs: {:?}", outputs);
+        outputs
+    }
+
+    /// Returns the output shown by a CRTC, or `None` if it is disabled.
+    fn get_crtc_output(&self, crtc: randr::Crtc, timestamp: xcb::Timestamp) -> Option<Output> {
+        let info = randr::get_crtc_info(&self.conn, crtc, timestamp)
+            .get_reply()
+            .ok()?;
+        let output: randr::Output = *info.outputs().first()?;
+        if info.width() == 0 || info.height() == 0 {
+            return None;
+        }
+        let name: String = randr::get_output_info(&self.conn, output, timestamp)
+            .get_reply()
+            .map(|reply| String::from_utf8_lossy(reply.name()).into_owned())
+            .unwrap_or_else(|_| format!("crtc-{}", crtc));
+        Some(Output {
+            name,
+            geometry: Viewport {
+                x: info.x() as u32,
+                y: info.y() as u32,
+                width: u32::from(info.width()),
+                height: u32::from(info.height()),
+            },
+        })
+    }
+
+    /// Returns the window's title, preferring the UTF-8 _NET_WM_NAME over the
+    /// ICCCM WM_NAME.
+    pub fn get_window_title(&self, window_id: &WindowId) -> Option<String> {
+        ewmh::get_wm_name(&self.conn, window_id.to_x())
+            .get_reply()
+            .map(|reply| reply.string().to_string())
+            .or_else(|_| {
+                icccm::get_wm_name(&self.conn, window_id.to_x())
+                    .get_reply()
+                    .map(|reply| reply.name().to_string())
+            })
+            .ok()
+    }
+
+    /// Returns the (instance, class) pair from the window's WM_CLASS.
+    pub fn get_window_class(&self, window_id: &WindowId) -> Option<(String, String)> {
+        icccm::get_wm_class(&self.conn, window_id.to_x())
+            .get_reply()
+            .map(|reply| (reply.instance().to_string(), reply.class().to_string()))
+            .ok()
+    }
+
     /// Map a window.
     pub fn map_window(&self, window_id: &WindowId) {
         xcb::map_window(&self.conn, window_id.to_x());
@@ -342,23 +762,27 @@ impl Connection {
         xcb::unmap_window(&self.conn, window_id.to_x());
     }
 
-    /// Registers for key events.
+    /// Registers for key events, whichever lock keys are on.
     ///
     /// If it fails to register any of the keys, it will log an error and continue.
     pub fn enable_window_key_events(&self, window_id: &WindowId, key_handlers: &KeyHandlers) {
-        let key_symbols = KeySymbols::new(&self.conn);
-        for key in key_handlers.key_combos() {
-            match key_symbols.get_keycode(key.keysym).next() {
-                Some(keycode) => {
-                    xcb::grab_key(
-                        &self.conn,
-                        false,
-                        window_id.to_x(),
-                        key.mod_mask as u16,
-                        keycode,
-                        xcb::GRAB_MODE_ASYNC as u8,
-                        xcb::GRAB_MODE_ASYNC as u8,
-                    );
+        let lock_combinations: Vec<ModMask> = self.lock_combinations();
+        let combos: Vec<&KeyCombo> = key_handlers.key_combos();
+        let keysyms: Vec<xcb::Keysym> = combos.iter().map(|key| key.keysym).collect();
+        for (key, keycodes) in combos.iter().zip(self.keycodes(&keysyms)) {
+            match keycodes.first().copied() {
+                Some((keycode, shift)) => {
+                    for locks in lock_combinations.iter() {
+                        xcb::grab_key(
+                            &self.conn,
+                            false,
+                            window_id.to_x(),
+                            (key.mod_mask | shift | locks) as u16,
+                            keycode,
+                            xcb::GRAB_MODE_ASYNC as u8,
+                            xcb::GRAB_MODE_ASYNC as u8,
+                        );
+                    }
                 }
                 None => {
                     error!(
@@ -370,14 +794,201 @@ impl Connection {
         }
     }
 
+    /// Sends every key press to us until `ungrab_keyboard()`, returning
+    /// whether the grab succeeded.
+    pub fn grab_keyboard(&self) -> bool {
+        let reply = xcb::grab_keyboard(
+            &self.conn,
+            false,
+            self.root.to_x(),
+            xcb::CURRENT_TIME,
+            xcb::GRAB_MODE_ASYNC as u8,
+            xcb::GRAB_MODE_ASYNC as u8,
+        )
+        .get_reply();
+        match reply {
+            Ok(reply) if u32::from(reply.status()) == xcb::GRAB_STATUS_SUCCESS => true,
+            _ => {
+                error!("Could not grab the keyboard");
+                false
+            }
+        }
+    }
+
+    pub fn ungrab_keyboard(&self) {
+        xcb::ungrab_keyboard(&self.conn, xcb::CURRENT_TIME);
+    }
+
+    /// Releases all of the key grabs on a window.
+    pub fn disable_window_key_events(&self, window_id: &WindowId) {
+        xcb::ungrab_key(
+            &self.conn,
+            xcb::GRAB_ANY as u8,
+            window_id.to_x(),
+            xcb::MOD_MASK_ANY as u16,
+        );
+    }
+
+    /// Registers for the button presses used by mouse bindings.
+    ///
+    /// Pointer motion and button releases are reported for as long as the
+    /// button is held, so that windows can be dragged. As with keys, the
+    /// buttons are grabbed whichever lock keys are on.
+    pub fn enable_window_mouse_events(&self, window_id: &WindowId, mouse_handlers: &MouseHandlers) {
+        let lock_combinations: Vec<ModMask> = self.lock_combinations();
+        for combo in mouse_handlers.mouse_combos() {
+            for locks in lock_combinations.iter() {
+                xcb::grab_button(
+                    &self.conn,
+                    false,
+                    window_id.to_x(),
+                    (xcb::EVENT_MASK_BUTTON_PRESS
+                        | xcb::EVENT_MASK_BUTTON_RELEASE
+                        | xcb::EVENT_MASK_POINTER_MOTION) as u16,
+                    xcb::GRAB_MODE_ASYNC as u8,
+                    xcb::GRAB_MODE_ASYNC as u8,
+                    xcb::NONE,
+                    xcb::NONE,
+                    combo.button,
+                    (combo.mod_mask | locks) as u16,
+                );
+            }
+        }
+    }
+
+    /// Releases the button grabs made by `enable_window_mouse_events()`,
+    /// leaving the click to focus grabs for other buttons and mods in place.
+    pub fn disable_window_mouse_events(
+        &self,
+        window_id: &WindowId,
+        mouse_handlers: &MouseHandlers,
+    ) {
+        let lock_combinations: Vec<ModMask> = self.lock_combinations();
+        for combo in mouse_handlers.mouse_combos() {
+            for locks in lock_combinations.iter() {
+                xcb::ungrab_button(
+                    &self.conn,
+                    combo.button,
+                    window_id.to_x(),
+                    (combo.mod_mask | locks) as u16,
+                );
+            }
+        }
+    }
+
+    /// Returns the top-level window under the pointer, if any.
+    pub fn window_under_pointer(&self) -> Option<WindowId> {
+        xcb::query_pointer(&self.conn, self.root.to_x())
+            .get_reply()
+            .ok()
+            .map(|reply| reply.child())
+            .filter(|&child| child != xcb::NONE)
+            .map(WindowId)
+    }
+
     pub fn enable_window_tracking(&self, window_id: &WindowId) {
         let values = [(
             xcb::CW_EVENT_MASK,
-            xcb::EVENT_MASK_ENTER_WINDOW | xcb::EVENT_MASK_STRUCTURE_NOTIFY,
+            xcb::EVENT_MASK_ENTER_WINDOW
+                | xcb::EVENT_MASK_STRUCTURE_NOTIFY
+                | xcb::EVENT_MASK_PROPERTY_CHANGE,
         )];
         xcb::change_window_attributes(&self.conn, window_id.to_x(), &values);
     }
 
+    /// Grabs the button presses on a window made without any mods held, so
+    /// that clicking it can focus it.
+    ///
+    /// The pointer is frozen when the grab activates and must be released with
+    /// `replay_pointer()`.
+    ///
+    /// Presses with mods held are left to the grabs made by
+    /// `enable_window_mouse_events()`, so the two only overlap for a mouse
+    /// binding without mods. Grabbing the same button and mods again replaces
+    /// the earlier grab, so this must be called before
+    /// `enable_window_mouse_events()` for such a binding to win.
+    pub fn enable_click_to_focus(&self, window_id: &WindowId) {
+        for locks in self.lock_combinations().iter() {
+            xcb::grab_button(
+                &self.conn,
+                false,
+                window_id.to_x(),
+                xcb::EVENT_MASK_BUTTON_PRESS as u16,
+                xcb::GRAB_MODE_SYNC as u8,
+                xcb::GRAB_MODE_ASYNC as u8,
+                xcb::NONE,
+                xcb::NONE,
+                xcb::BUTTON_INDEX_ANY as u8,
+                *locks as u16,
+            );
+        }
+    }
+
+    /// Releases the grabs made by `enable_click_to_focus()`, along with any
+    /// mouse binding grabs without mods.
+    pub fn disable_click_to_focus(&self, window_id: &WindowId) {
+        for locks in self.lock_combinations().iter() {
+            xcb::ungrab_button(
+                &self.conn,
+                xcb::BUTTON_INDEX_ANY as u8,
+                window_id.to_x(),
+                *locks as u16,
+            );
+        }
+    }
+
+    /// Releases a pointer frozen by a button grab, passing the click on to the
+    /// window underneath.
+    pub fn replay_pointer(&self) {
+        xcb::allow_events(
+            &self.conn,
+            xcb::ALLOW_REPLAY_POINTER as u8,
+            xcb::CURRENT_TIME,
+        );
+    }
+
+    /// Moves the pointer to a point on the root window.
+    pub fn warp_pointer_to_position(&self, (x, y): PointerPosition) {
+        xcb::warp_pointer(&self.conn, xcb::NONE, self.root.to_x(), 0, 0, 0, 0, x, y);
+    }
+
+    /// Moves the pointer to the centre of a window.
+    pub fn warp_pointer_to(&self, window_id: &WindowId) {
+        if let Some(geometry) = self.get_window_rect(window_id) {
+            xcb::warp_pointer(
+                &self.conn,
+                xcb::NONE,
+                window_id.to_x(),
+                0,
+                0,
+                0,
+                0,
+                (geometry.width / 2) as i16,
+                (geometry.height / 2) as i16,
+            );
+        }
+    }
+
+    /// Throws away any enter events caused by requests made so far.
+    ///
+    /// Moving and mapping windows under a stationary pointer generates enter
+    /// events, which would otherwise steal the focus under focus-follows-mouse.
+    /// This waits for the server to process every outstanding request and
+    /// drops the resulting enter events, keeping any other events for the
+    /// `EventLoop`.
+    pub fn discard_enter_events(&self) {
+        if let Err(error) = xcb::get_input_focus(&self.conn).get_reply() {
+            error!("Failed to sync with X server: {:?}", error);
+            return;
+        }
+        let mut deferred = self.deferred_events.borrow_mut();
+        while let Some(event) = self.conn.poll_for_event() {
+            if event.response_type() != xcb::ENTER_NOTIFY {
+                deferred.push_back(event);
+            }
+        }
+    }
+
     pub fn disable_window_tracking(&self, window_id: &WindowId) {
         let values = [(xcb::CW_EVENT_MASK, xcb::EVENT_MASK_NO_EVENT)];
         xcb::change_window_attributes(&self.conn, window_id.to_x(), &values);
@@ -398,8 +1009,51 @@ impl Connection {
         ewmh::set_active_window(&self.conn, self.screen_idx, xcb::NONE);
     }
 
-    pub fn get_event_loop(&self) -> EventLoop<'_> {
-        EventLoop { connection: self }
+    /// Returns an event loop over X events, also yielding requests from the
+    /// control socket when one is given.
+    pub fn get_event_loop<'a>(
+        &'a self,
+        ipc: Option<&'a ipc::Server>,
+        signals: Option<&'a Signals>,
+    ) -> EventLoop<'a> {
+        EventLoop {
+            connection: self,
+            ipc,
+            signals,
+            pending: VecDeque::new(),
+            key_symbols: KeySymbols::new(&self.conn),
+        }
+    }
+}
+
+/// A physical display, as reported by RandR.
+#[derive(Clone, Debug)]
+pub struct Output {
+    pub name: String,
+    /// The area of the root window shown on the output.
+    pub geometry: Viewport,
+}
+
+/// The pointer position, relative to the root window.
+pub type PointerPosition = (i16, i16);
+
+/// How a client asked for its _NET_WM_STATE to be changed.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StateAction {
+    Remove,
+    Add,
+    Toggle,
+}
+
+impl StateAction {
+    /// Returns whether a state should be set after this action, given whether
+    /// it is set now.
+    pub fn apply(self, current: bool) -> bool {
+        match self {
+            StateAction::Remove => false,
+            StateAction::Add => true,
+            StateAction::Toggle => !current,
+        }
     }
 }
 
@@ -408,7 +1062,39 @@ pub enum Event {
     MapRequest(WindowId),
     UnmapNotify(WindowId),
     DestroyNotify(WindowId),
-    KeyPress(KeyCombo),
+    /// A key was pressed, read each way it could be bound: see
+    /// `EventLoop::key_combos()`.
+    KeyPress(Vec<KeyCombo>),
+    KeyRelease(Vec<KeyCombo>),
+    EnterNotify(WindowId),
+    ButtonPress(WindowId, MouseCombo, PointerPosition),
+    MotionNotify(PointerPosition),
+    ButtonRelease,
+    /// The keyboard or modifier mapping changed, e.g. with `setxkbmap`, so
+    /// keys need to be grabbed again.
+    KeyboardMappingChanged,
+    /// A client asked to change the _NET_WM_STATE of a window.
+    WindowStateRequest(WindowId, StateAction, Vec<WindowState>),
+    /// A pager or client asked for a window to be focused (_NET_ACTIVE_WINDOW).
+    ActivateWindowRequest(WindowId),
+    /// A pager asked to switch to the desktop with this index
+    /// (_NET_CURRENT_DESKTOP).
+    CurrentDesktopRequest(u32),
+    /// A pager asked for a window to be closed (_NET_CLOSE_WINDOW).
+    CloseWindowRequest(WindowId),
+    /// A pager asked to move a window to the desktop with this index
+    /// (_NET_WM_DESKTOP).
+    WindowDesktopRequest(WindowId, u32),
+    /// The WM_HINTS or _NET_WM_STATE of a window changed, which may change
+    /// whether it's asking for attention.
+    UrgencyHintsChanged(WindowId),
+    /// Outputs were added, removed or reconfigured (RRScreenChangeNotify).
+    ScreenChange,
+    IpcRequest(ipc::Request),
+    /// SIGHUP was received, asking for the config to be read again.
+    Hangup,
+    /// SIGCHLD was received, so child processes need to be reaped.
+    ChildExited,
 }
 
 /// An iterator that yields events from the X event loop.
@@ -416,6 +1102,12 @@ pub enum Event {
 /// Use `Connection::get_event_loop()` to get one.
 pub struct EventLoop<'a> {
     connection: &'a Connection,
+    ipc: Option<&'a ipc::Server>,
+    signals: Option<&'a Signals>,
+    pending: VecDeque<Event>,
+    /// The keyboard mapping used to look up the keysyms of key presses,
+    /// refreshed when it changes.
+    key_symbols: KeySymbols<'a>,
 }
 
 impl<'a> Iterator for EventLoop<'a> {
@@ -423,15 +1115,30 @@ impl<'a> Iterator for EventLoop<'a> {
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
+            if let Some(pending) = self.pending.pop_front() {
+                return Some(pending);
+            }
+
             // Flush any pending operations that came out of the event we (might
             // have) just yielded.
             self.connection.flush();
 
-            let event = self
-                .connection
-                .conn
-                .wait_for_event()
-                .expect("wait_for_event() returned None: IO error?");
+            let deferred = self.connection.deferred_events.borrow_mut().pop_front();
+            let event = match deferred.or_else(|| self.connection.conn.poll_for_event()) {
+                Some(event) => event,
+                None => {
+                    self.connection
+                        .conn
+                        .has_error()
+                        .expect("poll_for_event() returned None: IO error?");
+                    self.wait_for_input();
+                    continue;
+                }
+            };
+
+            if Some(event.response_type()) == self.screen_change_event() {
+                return Some(Event::ScreenChange);
+            }
 
             unsafe {
                 let propagate = match event.response_type() {
@@ -440,6 +1147,14 @@ impl<'a> Iterator for EventLoop<'a> {
                     xcb::UNMAP_NOTIFY => self.on_unmap_notify(xcb::cast_event(&event)),
                     xcb::DESTROY_NOTIFY => self.on_destroy_notify(xcb::cast_event(&event)),
                     xcb::KEY_PRESS => self.on_key_press(xcb::cast_event(&event)),
+                    xcb::KEY_RELEASE => self.on_key_release(xcb::cast_event(&event)),
+                    xcb::MAPPING_NOTIFY => self.on_mapping_notify(xcb::cast_event(&event)),
+                    xcb::ENTER_NOTIFY => self.on_enter_notify(xcb::cast_event(&event)),
+                    xcb::PROPERTY_NOTIFY => self.on_property_notify(xcb::cast_event(&event)),
+                    xcb::BUTTON_PRESS => self.on_button_press(xcb::cast_event(&event)),
+                    xcb::MOTION_NOTIFY => self.on_motion_notify(xcb::cast_event(&event)),
+                    xcb::BUTTON_RELEASE => self.on_button_release(xcb::cast_event(&event)),
+                    xcb::CLIENT_MESSAGE => self.on_client_message(xcb::cast_event(&event)),
                     _ => None,
                 };
 
@@ -452,6 +1167,62 @@ impl<'a> Iterator for EventLoop<'a> {
 }
 
 impl<'a> EventLoop<'a> {
+    /// Returns the event number of RRScreenChangeNotify, which isn't known
+    /// until the RandR extension has been queried.
+    fn screen_change_event(&self) -> Option<u8> {
+        self.connection
+            .randr_event_base
+            .map(|base| base + randr::SCREEN_CHANGE_NOTIFY)
+    }
+
+    /// Blocks until the X connection, the signal pipe or the control socket
+    /// has something to read, or a control client can take the rest of its
+    /// output, queueing up any signals or control requests received.
+    fn wait_for_input(&mut self) {
+        let ipc_fds: Vec<(RawFd, libc::c_short)> =
+            self.ipc.map(|ipc| ipc.fds()).unwrap_or_default();
+        let signal_fd: Option<RawFd> = self.signals.map(Signals::fd);
+        let mut poll_fds: Vec<libc::pollfd> = std::iter::once(self.connection.conn.as_raw_fd())
+            .chain(signal_fd)
+            .map(|fd| (fd, libc::POLLIN))
+            .chain(ipc_fds)
+            .map(|(fd, events)| libc::pollfd {
+                fd,
+                events,
+                revents: 0,
+            })
+            .collect();
+
+        let ready: i32 =
+            unsafe { libc::poll(poll_fds.as_mut_ptr(), poll_fds.len() as libc::nfds_t, -1) };
+        if ready < 0 {
+            let error = std::io::Error::last_os_error();
+            if error.kind() != std::io::ErrorKind::Interrupted {
+                error!("Failed to poll for events: {}", error);
+            }
+            return;
+        }
+
+        if let Some(signals) = self.signals {
+            if poll_fds[1].revents != 0 {
+                let received: Received = signals.take();
+                if received.child_exited {
+                    self.pending.push_back(Event::ChildExited);
+                }
+                if received.hangup {
+                    self.pending.push_back(Event::Hangup);
+                }
+            }
+        }
+        let ipc_start: usize = if signal_fd.is_some() { 2 } else { 1 };
+        if let Some(ipc) = self.ipc {
+            if poll_fds[ipc_start..].iter().any(|p| p.revents != 0) {
+                self.pending
+                    .extend(ipc.read_requests().into_iter().map(Event::IpcRequest));
+            }
+        }
+    }
+
     fn on_configure_request(&self, event: &xcb::ConfigureRequestEvent) -> Option<Event> {
         // This request is not interesting for us: grant it unchanged.
         // Build a request with all attributes set, then filter out to only include
@@ -501,12 +1272,171 @@ impl<'a> EventLoop<'a> {
         Some(Event::DestroyNotify(WindowId(event.window())))
     }
 
+    fn on_mapping_notify(&self, event: &xcb::MappingNotifyEvent) -> Option<Event> {
+        match u32::from(event.request()) {
+            xcb::MAPPING_KEYBOARD | xcb::MAPPING_MODIFIER => {
+                self.key_symbols.refresh_keyboard_mapping(event);
+                Some(Event::KeyboardMappingChanged)
+            }
+            _ => None,
+        }
+    }
+
     fn on_key_press(&self, event: &xcb::KeyPressEvent) -> Option<Event> {
-        let key_symbols = KeySymbols::new(&self.connection.conn);
-        let keysym = key_symbols.press_lookup_keysym(event, 0);
-        let mod_mask = u32::from(event.state());
-        let key = KeyCombo { mod_mask, keysym };
-        Some(Event::KeyPress(key))
+        let keysyms = (
+            self.key_symbols.press_lookup_keysym(event, 0),
+            self.key_symbols.press_lookup_keysym(event, 1),
+        );
+        Some(Event::KeyPress(self.key_combos(
+            keysyms,
+            event.state(),
+            false,
+        )))
+    }
+
+    fn on_key_release(&self, event: &xcb::KeyReleaseEvent) -> Option<Event> {
+        // Only the key really being let go of should run release bindings.
+        if self.is_repeat(event) {
+            return None;
+        }
+        let keysyms = (
+            self.key_symbols.release_lookup_keysym(event, 0),
+            self.key_symbols.release_lookup_keysym(event, 1),
+        );
+        Some(Event::KeyRelease(self.key_combos(
+            keysyms,
+            event.state(),
+            true,
+        )))
+    }
+
+    /// Returns the ways a key can be bound, given the keysyms in the first
+    /// two columns of its keycode: by the unshifted keysym with the mods
+    /// held, such as `Shift+1`, and, with Shift held, by the keysym it types
+    /// without Shift among the mods, such as `exclam`.
+    fn key_combos(
+        &self,
+        (unshifted, shifted): (xcb::Keysym, xcb::Keysym),
+        state: u16,
+        release: bool,
+    ) -> Vec<KeyCombo> {
+        let mod_mask: ModMask = self.connection.binding_mods(state);
+        let mut combos: Vec<KeyCombo> = vec![KeyCombo {
+            mod_mask,
+            keysym: unshifted,
+            release,
+        }];
+        if mod_mask & xcb::MOD_MASK_SHIFT != 0 && shifted != 0 && shifted != unshifted {
+            combos.push(KeyCombo {
+                mod_mask: mod_mask & !xcb::MOD_MASK_SHIFT,
+                keysym: shifted,
+                release,
+            });
+        }
+        combos
+    }
+
+    /// Returns whether a key release is followed straight away by a press of
+    /// the same key at the same time, which is how X repeats a held key.
+    fn is_repeat(&self, event: &xcb::KeyReleaseEvent) -> bool {
+        let mut deferred = self.connection.deferred_events.borrow_mut();
+        if deferred.is_empty() {
+            match self.connection.conn.poll_for_event() {
+                Some(next) => deferred.push_back(next),
+                None => return false,
+            }
+        }
+        deferred.front().is_some_and(|next| {
+            if next.response_type() != xcb::KEY_PRESS {
+                return false;
+            }
+            let press: &xcb::KeyPressEvent = unsafe { xcb::cast_event(next) };
+            press.detail() == event.detail() && press.time() == event.time()
+        })
+    }
+
+    fn on_enter_notify(&self, event: &xcb::EnterNotifyEvent) -> Option<Event> {
+        // Ignore crossings caused by grabs, and the pointer moving from a
+        // window into one of its own children.
+        if u32::from(event.mode()) != xcb::NOTIFY_MODE_NORMAL
+            || u32::from(event.detail()) == xcb::NOTIFY_DETAIL_INFERIOR
+        {
+            return None;
+        }
+        Some(Event::EnterNotify(WindowId(event.event())))
+    }
+
+    fn on_property_notify(&self, event: &xcb::PropertyNotifyEvent) -> Option<Event> {
+        if event.atom() == xcb::ATOM_WM_HINTS || event.atom() == self.connection.conn.WM_STATE() {
+            Some(Event::UrgencyHintsChanged(WindowId(event.window())))
+        } else {
+            None
+        }
+    }
+
+    fn on_button_press(&self, event: &xcb::ButtonPressEvent) -> Option<Event> {
+        // If the pointer was frozen by the click-to-focus grab, let the click
+        // through to the window straight away. Mouse bindings are grabbed
+        // asynchronously and so never freeze the pointer, and as the click to
+        // focus grab is replaced by them for the same button and mods, a click
+        // which starts a drag is never replayed and this has no effect.
+        self.connection.replay_pointer();
+        let combo = MouseCombo {
+            mod_mask: self.connection.binding_mods(event.state()),
+            button: event.detail(),
+        };
+        Some(Event::ButtonPress(
+            WindowId(event.event()),
+            combo,
+            (event.root_x(), event.root_y()),
+        ))
+    }
+
+    fn on_client_message(&self, event: &xcb::ClientMessageEvent) -> Option<Event> {
+        if event.format() != 32 {
+            return None;
+        }
+        let window_id = WindowId(event.window());
+        let data: &[u32] = event.data().data32();
+        if event.type_() == self.connection.conn.WM_STATE() {
+            let action = match data[0] {
+                ewmh::STATE_REMOVE => StateAction::Remove,
+                ewmh::STATE_ADD => StateAction::Add,
+                ewmh::STATE_TOGGLE => StateAction::Toggle,
+                other => {
+                    error!("Unknown _NET_WM_STATE action {} for {}", other, window_id);
+                    return None;
+                }
+            };
+            let states: Vec<WindowState> = data[1..3]
+                .iter()
+                .filter_map(|atom| self.connection.window_state_lookup.get(atom).cloned())
+                .collect();
+            Some(Event::WindowStateRequest(window_id, action, states))
+        } else if event.type_() == self.connection.conn.ACTIVE_WINDOW() {
+            Some(Event::ActivateWindowRequest(window_id))
+        } else if event.type_() == self.connection.conn.CURRENT_DESKTOP() {
+            Some(Event::CurrentDesktopRequest(data[0]))
+        } else if event.type_() == self.connection.conn.CLOSE_WINDOW() {
+            Some(Event::CloseWindowRequest(window_id))
+        } else if event.type_() == self.connection.conn.WM_DESKTOP() {
+            Some(Event::WindowDesktopRequest(window_id, data[0]))
+        } else {
+            debug!(
+                "Ignoring client message {} for {}",
+                event.type_(),
+                window_id
+            );
+            None
+        }
+    }
+
+    fn on_motion_notify(&self, event: &xcb::MotionNotifyEvent) -> Option<Event> {
+        Some(Event::MotionNotify((event.root_x(), event.root_y())))
+    }
+
+    fn on_button_release(&self, _event: &xcb::ButtonReleaseEvent) -> Option<Event> {
+        Some(Event::ButtonRelease)
     }
 }
 
@@ -519,4 +1449,16 @@ impl WindowGeometry {
             height: viewport.height,
         }
     }
+
+    /// Returns the size a window must be for it and a border of the given
+    /// width to fill this geometry. X places the border outside the window,
+    /// with the window's position being that of the border's top left corner.
+    pub fn inside_border(&self, border_width: u32) -> Self {
+        Self {
+            x: self.x,
+            y: self.y,
+            width: cmp::max(1, self.width.saturating_sub(border_width * 2)),
+            height: cmp::max(1, self.height.saturating_sub(border_width * 2)),
+        }
+    }
 }